@@ -1,87 +1,84 @@
-use std::io::{stdin, Read};
+use std::thread;
 
-pub fn run() {
-    let program = read_memory();
+use clap::{App, Arg, ArgMatches, SubCommand};
 
-    for i in 0..100 {
-        for j in 0..100 {
-            let mut memory = program.clone();
-            memory[1] = i;
-            memory[2] = j;
-            run_computer(&mut memory);
+use crate::intcode;
+use crate::intcode::limit::{self, Limits};
+use crate::intcode::solver::{self, Search};
 
-            if memory[0] == 19690720 {
-                println!("{}", 100 * memory[1] + memory[2]);
-                break;
-            }
-        }
-    }
+pub fn subcommand() -> App<'static, 'static> {
+    SubCommand::with_name("day2")
+        .arg(
+            Arg::with_name("nouns")
+                .long("nouns")
+                .takes_value(true)
+                .default_value("0..100")
+                .help("Values to try at address 1, as a half-open range"),
+        )
+        .arg(
+            Arg::with_name("verbs")
+                .long("verbs")
+                .takes_value(true)
+                .default_value("0..100")
+                .help("Values to try at address 2, as a half-open range"),
+        )
+        .arg(
+            Arg::with_name("target")
+                .long("target")
+                .takes_value(true)
+                .allow_hyphen_values(true)
+                .default_value("19690720")
+                .help("Value the program has to leave at address 0"),
+        )
+        .arg(
+            Arg::with_name("all")
+                .long("all")
+                .help("Prints every noun and verb pair instead of the first"),
+        )
+        .arg(
+            Arg::with_name("formula")
+                .long("formula")
+                .help("Prints address 0 as a formula in the noun [1] and verb [2]"),
+        )
+        .arg(
+            Arg::with_name("workers")
+                .long("workers")
+                .takes_value(true)
+                .help("Number of threads searching, one per CPU if omitted"),
+        )
+        .args(&limit::args())
 }
 
-fn read_memory() -> Vec<u64> {
-    let mut s = Vec::new();
-    stdin()
-        .read_to_end(&mut s)
-        .expect("did not enter a correct string");
-
-    let input_string = std::str::from_utf8(&s).expect("invalid utf8");
-    let memory_values: Vec<u64> = input_string
-        .split(",")
-        .filter_map(|value| value.parse::<u64>().ok())
-        .collect();
-    memory_values
-}
+pub fn run(matches: &ArgMatches) {
+    let range = |name| {
+        solver::parse_range(matches.value_of(name).unwrap())
+            .unwrap_or_else(|| panic!("{} must be a range like 0..100", name))
+    };
+    let target = matches
+        .value_of("target")
+        .unwrap()
+        .parse()
+        .expect("target must be a number");
 
-fn run_computer(mut memory: &mut Vec<u64>) {
-    let mut program_counter = 0;
-    loop {
-        match memory.get(program_counter) {
-            Some(&value) => match value {
-                1 => {
-                    perform_operation(&mut program_counter, &mut memory, |a, b| a + b);
-                }
-                2 => {
-                    perform_operation(&mut program_counter, &mut memory, |a, b| a * b);
-                }
-                99 => break,
-                _ => break,
-            },
-            None => break,
-        }
+    let mut search = Search::new(intcode::read_program());
+    search.parameter(1, range("nouns"));
+    search.parameter(2, range("verbs"));
+    search.workers = match matches.value_of("workers") {
+        Some(workers) => workers.parse().expect("workers must be a number"),
+        None => thread::available_parallelism().map_or(1, |n| n.get()),
+    };
+    let limits = limit::from_matches(matches);
+    if limits != Limits::default() {
+        search.limits = limits;
     }
-}
 
-fn perform_operation(
-    program_counter: &mut usize,
-    memory: &mut Vec<u64>,
-    mut operation: impl FnMut(u64, u64) -> u64,
-) {
-    if let (Some(result_position), Some(add1_position), Some(add2_position)) = (
-        memory
-            .get(*program_counter + 3)
-            .cloned()
-            .map(|v| v as usize),
-        memory
-            .get(*program_counter + 1)
-            .cloned()
-            .map(|v| v as usize),
-        memory
-            .get(*program_counter + 2)
-            .cloned()
-            .map(|v| v as usize),
-    ) {
-        if let (Some(add1), Some(add2), Some(result)) = (
-            memory.get(add1_position).cloned(),
-            memory.get(add2_position).cloned(),
-            memory.get_mut(result_position),
-        ) {
-            *result = operation(add1, add2);
-        } else {
-            panic!("invalid memory positions");
+    if matches.is_present("formula") {
+        match search.formula(0) {
+            Ok(formula) => println!("{}", formula),
+            Err(e) => println!("no formula: {}", e),
         }
-
-        *program_counter = *program_counter + 4;
-    } else {
-        panic!("overflow at pc {}", program_counter);
+    }
+    for solution in search.memory_solutions(0, target, !matches.is_present("all")) {
+        println!("{}", 100 * solution[0] + solution[1]);
     }
 }
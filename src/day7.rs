@@ -1,41 +1,254 @@
-use std::collections::VecDeque;
-use std::io::{stdin, Error as IoError, ErrorKind, Read};
+use std::cmp::Reverse;
+use std::collections::{HashMap, VecDeque};
+use std::io::{stdin, Read};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::Duration;
 
+use clap::{App, Arg, ArgMatches, SubCommand};
 use permutator::Permutation;
 
-pub fn run() {
+use crate::intcode::limit::{self, Limits};
+use crate::intcode::network::{Network, NetworkState, ThreadedError};
+use crate::intcode::snapshot::Snapshot;
+use crate::intcode::{parse_program, IntcodeError, Machine, RunMode};
+
+/// How long a threaded run of one permutation may take without `--timeout`.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How the amplifiers are wired up.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Mode {
+    /// Part 1, each amplifier runs once.
+    Series,
+    /// Part 2, the last amplifier feeds back into the first.
+    Feedback,
+}
+
+/// What `best_phases` found.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct Search {
+    /// The best phase settings and their signal, if any permutation finished.
+    pub best: Option<(Vec<i64>, i64)>,
+    /// Permutations given up on for running into the limits, in order.
+    pub runaway: Vec<Vec<i64>>,
+    /// Permutations that stopped without a signal, in order, with the reason.
+    pub failed: Vec<(Vec<i64>, String)>,
+}
+
+/// Why a permutation gave no signal.
+#[derive(Clone, PartialEq, Eq, Debug)]
+enum Skipped {
+    Runaway,
+    Failed(String),
+}
+
+pub fn subcommand() -> App<'static, 'static> {
+    SubCommand::with_name("day7")
+        .arg(
+            Arg::with_name("phases")
+                .long("phases")
+                .takes_value(true)
+                .default_value("5,6,7,8,9")
+                .help("Comma-separated phase settings, one per amplifier"),
+        )
+        .arg(
+            Arg::with_name("mode")
+                .long("mode")
+                .takes_value(true)
+                .possible_values(&["series", "feedback"])
+                .default_value("feedback")
+                .help("Whether the last amplifier feeds back into the first"),
+        )
+        .arg(
+            Arg::with_name("workers")
+                .long("workers")
+                .takes_value(true)
+                .help("Number of threads searching permutations, one per CPU if omitted"),
+        )
+        .arg(
+            Arg::with_name("threads")
+                .long("threads")
+                .help("Runs each amplifier on its own thread"),
+        )
+        .args(&limit::args())
+}
+
+pub fn run(matches: &ArgMatches) {
     let program = read_memory();
-    let max = [5, 6, 7, 8, 9]
-        .permutation()
-        .map(|perm| {
-            let mut feed_forward = 0;
-            let mut mem = vec![];
-            let mut program_counters = vec![];
-            let mut inputs = vec![];
-            for i in 0..5 {
-                mem.push(program[0].clone());
-                program_counters.push(0);
-                inputs.push(VecDeque::from(vec![perm[i]]));
-            }
-            loop {
-                for i in 0..5 {
-                    inputs[i].push_back(feed_forward);
-                    match run_computer(&mut program_counters[i], &mut mem[i], &mut inputs[i])
-                        .expect("program error")
-                    {
-                        ProgramResult::Output(output) => feed_forward = output,
-                        ProgramResult::Finished => {
-                            if i == 4 {
-                                return feed_forward;
-                            }
-                        }
-                    }
+    let phases = parse_program(matches.value_of("phases").unwrap());
+    let mode = match matches.value_of("mode").unwrap() {
+        "series" => Mode::Series,
+        _ => Mode::Feedback,
+    };
+    let workers = match matches.value_of("workers") {
+        Some(workers) => workers.parse().expect("workers must be a number"),
+        None => thread::available_parallelism().map_or(1, |n| n.get()),
+    };
+    let threads = matches.is_present("threads");
+    let limits = limit::from_matches(matches);
+
+    let search = best_phases(&program[0], &phases, mode, workers, threads, limits);
+    if !search.runaway.is_empty() {
+        eprintln!(
+            "skipped {} permutations that ran into the limits, starting with {}",
+            search.runaway.len(),
+            join(&search.runaway[0])
+        );
+    }
+    if let Some((phases, reason)) = search.failed.first() {
+        eprintln!(
+            "skipped {} permutations that gave no signal, starting with {}: {}",
+            search.failed.len(),
+            join(phases),
+            reason
+        );
+    }
+    match search.best {
+        Some((best, signal)) => println!("{} (phases {})", signal, join(&best)),
+        None if phases.is_empty() => println!("no phases given"),
+        None => println!("no permutation finished"),
+    }
+}
+
+fn join(phases: &[i64]) -> String {
+    let phases: Vec<String> = phases.iter().map(i64::to_string).collect();
+    phases.join(",")
+}
+
+/// Tries every order of `phases` on `workers` threads and returns the one
+/// giving the highest signal, the first in permutation order on a tie.
+/// Each amplifier runs under `limits`. Permutations where one runs into them,
+/// or that end without a signal, are skipped.
+pub fn best_phases(
+    program: &[i64],
+    phases: &[i64],
+    mode: Mode,
+    workers: usize,
+    threads: bool,
+    limits: Limits,
+) -> Search {
+    // every amplifier starts out identically for a given phase, so run that
+    // part only once per phase instead of once per permutation; snapshots
+    // leave output behind, so amplifiers that print before reading their
+    // signal start from the program instead
+    let primed: HashMap<i64, _> = phases
+        .iter()
+        .map(|&phase| {
+            let mut machine = Machine::new(program.to_vec());
+            machine.set_limits(limits);
+            machine.push_input(phase);
+            let start = machine.snapshot();
+            let primed = machine.run(RunMode::UntilInput).map(|_| {
+                if machine.output().is_empty() {
+                    machine.snapshot()
+                } else {
+                    start
                 }
-            }
+            });
+            (phase, primed)
         })
-        .max()
-        .unwrap();
-    println!("{}", max);
+        .collect();
+    let permutations: Vec<Vec<i64>> = if phases.is_empty() {
+        vec![]
+    } else {
+        // the iterator leaves out the order it starts from
+        let mut permutations = vec![phases.to_vec()];
+        permutations.extend(phases.to_vec().permutation());
+        permutations
+    };
+
+    let next = AtomicUsize::new(0);
+    let (best, mut skipped) = thread::scope(|scope| {
+        let searches: Vec<_> = (0..workers.max(1))
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut best = None;
+                    let mut skipped = vec![];
+                    loop {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        let perm = match permutations.get(index) {
+                            Some(perm) => perm,
+                            None => return (best, skipped),
+                        };
+                        match signal(&primed, perm, mode, threads, limits) {
+                            Ok(signal) => best = best.max(Some((signal, Reverse(index)))),
+                            Err(reason) => skipped.push((index, reason)),
+                        }
+                    }
+                })
+            })
+            .collect();
+        searches
+            .into_iter()
+            .map(|search| search.join().expect("search thread panicked"))
+            .fold((None, vec![]), |(best, mut skipped), (found, more)| {
+                skipped.extend(more);
+                (best.max(found), skipped)
+            })
+    });
+    skipped.sort_unstable_by_key(|&(index, _)| index);
+    let mut search = Search {
+        best: best.map(|(signal, Reverse(index))| (permutations[index].clone(), signal)),
+        ..Search::default()
+    };
+    for (index, reason) in skipped {
+        let perm = permutations[index].clone();
+        match reason {
+            Skipped::Runaway => search.runaway.push(perm),
+            Skipped::Failed(reason) => search.failed.push((perm, reason)),
+        }
+    }
+    search
+}
+
+/// The signal the last amplifier sends to the thrusters.
+fn signal(
+    primed: &HashMap<i64, Result<Snapshot<VecDeque<i64>>, IntcodeError>>,
+    perm: &[i64],
+    mode: Mode,
+    threads: bool,
+    limits: Limits,
+) -> Result<i64, Skipped> {
+    let mut amplifiers = vec![];
+    for phase in perm {
+        let snapshot = match &primed[phase] {
+            Ok(snapshot) => snapshot.clone(),
+            Err(IntcodeError::LimitExceeded { .. }) => return Err(Skipped::Runaway),
+            Err(e) => return Err(Skipped::Failed(e.to_string())),
+        };
+        let mut amplifier = Machine::from_snapshot(snapshot, vec![]);
+        amplifier.set_limits(limits);
+        amplifiers.push(amplifier);
+    }
+    let mut network = match mode {
+        Mode::Series => {
+            let mut network = Network::series(amplifiers);
+            network.push_input(0, 0);
+            network
+        }
+        Mode::Feedback => Network::feedback(amplifiers, 0),
+    };
+    let result = if threads {
+        network.run_threaded(limits.time.unwrap_or(TIMEOUT))
+    } else {
+        network.run().map_err(ThreadedError::Machine)
+    };
+    match result {
+        Ok(NetworkState::Halted) => match network.output(0).last() {
+            Some(&signal) => Ok(signal),
+            None => Err(Skipped::Failed("halted without a signal".to_string())),
+        },
+        // threads report what `run` calls `Waiting` as a deadlock
+        Ok(NetworkState::Waiting) | Err(ThreadedError::Deadlock { .. }) => Err(Skipped::Failed(
+            "amplifiers stuck waiting for input".to_string(),
+        )),
+        Err(ThreadedError::Timeout { .. }) => Err(Skipped::Runaway),
+        Err(ThreadedError::Machine(e)) if matches!(e.error, IntcodeError::LimitExceeded { .. }) => {
+            Err(Skipped::Runaway)
+        }
+        Err(e) => Err(Skipped::Failed(e.to_string())),
+    }
 }
 
 pub fn read_memory() -> Vec<Vec<i64>> {
@@ -48,7 +261,7 @@ pub fn read_memory() -> Vec<Vec<i64>> {
     let memory_values: Vec<Vec<i64>> = input_string
         .lines()
         .map(|line| {
-            line.split(",")
+            line.split(',')
                 .filter_map(|value| value.parse::<i64>().ok())
                 .collect()
         })
@@ -56,262 +269,89 @@ pub fn read_memory() -> Vec<Vec<i64>> {
     memory_values
 }
 
-pub fn run_computer(
-    mut program_counter: &mut usize,
-    mut memory: &mut Vec<i64>,
-    mut input: &mut VecDeque<i64>,
-) -> Result<ProgramResult, IoError> {
-    loop {
-        match perform_operation(&mut program_counter, &mut memory, &mut input) {
-            Ok(OperationResult::Output(value)) => return Ok(ProgramResult::Output(value)),
-            Ok(OperationResult::Finished) => return Ok(ProgramResult::Finished),
-            Ok(OperationResult::Continue) => continue,
-            Err(e) => return Err(e),
-        }
-    }
-}
-
-enum OperationResult {
-    Output(i64),
-    Continue,
-    Finished,
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-pub enum ProgramResult {
-    Output(i64),
-    Finished,
-}
+    #[test]
+    fn test_best_phases_series() {
+        let program = parse_program("3,15,3,16,1002,16,10,16,1,16,15,15,4,15,99,0,0");
+        let search = best_phases(
+            &program,
+            &[0, 1, 2, 3, 4],
+            Mode::Series,
+            3,
+            false,
+            Limits::default(),
+        );
+        assert_eq!(search.best, Some((vec![4, 3, 2, 1, 0], 43210)));
+        assert!(search.runaway.is_empty());
+    }
 
-fn load(address: usize, memory: &Vec<i64>, mode: AddressingMode) -> Result<i64, IoError> {
-    match mode {
-        AddressingMode::Indirect => memory
-            .get(
-                memory
-                    .get(address)
-                    .ok_or(IoError::new(ErrorKind::Other, "failed load"))?
-                    .clone() as usize,
+    #[test]
+    fn test_best_phases_feedback() {
+        let program = parse_program(
+            "3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,27,\
+             4,27,1001,28,-1,28,1005,28,6,99,0,0,5",
+        );
+        let expected = Some((vec![9, 8, 7, 6, 5], 139_629_729));
+        let limits = Limits::default();
+        let search = |workers, threads| {
+            best_phases(
+                &program,
+                &[5, 6, 7, 8, 9],
+                Mode::Feedback,
+                workers,
+                threads,
+                limits,
             )
-            .copied()
-            .ok_or(IoError::new(ErrorKind::Other, "failed load")),
-        AddressingMode::Immediate => memory
-            .get(address)
-            .copied()
-            .ok_or(IoError::new(ErrorKind::Other, "failed load")),
+        };
+        assert_eq!(search(1, false).best, expected);
+        assert_eq!(search(4, true).best, expected);
+        assert_eq!(
+            best_phases(&program, &[], Mode::Feedback, 4, false, limits),
+            Search::default()
+        );
     }
-}
 
-fn perform_operation(
-    program_counter: &mut usize,
-    memory: &mut Vec<i64>,
-    input: &mut VecDeque<i64>,
-) -> Result<OperationResult, IoError> {
-    let operation = parse_opcode(
-        memory
-            .get(*program_counter)
-            .ok_or(IoError::new(ErrorKind::Other, "pc overflow"))?
-            .clone(),
-    );
-    match operation {
-        Some(Operation::Addition {
-            summand1_mode: s1,
-            summand2_mode: s2,
-        }) => {
-            let summand1 = load(*program_counter + 1, &memory, s1)?;
-            let summand2 = load(*program_counter + 2, &memory, s2)?;
-            let destination =
-                load(*program_counter + 3, &memory, AddressingMode::Immediate)? as usize;
-            memory[destination] = summand1 + summand2;
-            *program_counter = *program_counter + 4;
-        }
-        Some(Operation::Multiplication {
-            factor1_mode: f1,
-            factor2_mode: f2,
-        }) => {
-            let factor1 = load(*program_counter + 1, &memory, f1)?;
-            let factor2 = load(*program_counter + 2, &memory, f2)?;
-            let destination =
-                load(*program_counter + 3, &memory, AddressingMode::Immediate)? as usize;
-            memory[destination] = factor1 * factor2;
-            *program_counter = *program_counter + 4;
-        }
-        Some(Operation::JumpIfTrue {
-            condition_mode,
-            value_mode,
-        }) => {
-            let condition = load(*program_counter + 1, &memory, condition_mode)?;
-            let value = load(*program_counter + 2, &memory, value_mode)? as usize;
-            *program_counter = if condition == 0 {
-                *program_counter + 3
-            } else {
-                value
-            }
-        }
-        Some(Operation::JumpIfFalse {
-            condition_mode,
-            value_mode,
-        }) => {
-            let condition = load(*program_counter + 1, &memory, condition_mode)?;
-            let value = load(*program_counter + 2, &memory, value_mode)? as usize;
-            *program_counter = if condition != 0 {
-                *program_counter + 3
-            } else {
-                value
-            }
-        }
-        Some(Operation::LessThan {
-            left_parameter_mode: s1,
-            right_parameter_mode: s2,
-        }) => {
-            let left_parameter = load(*program_counter + 1, &memory, s1)?;
-            let right_parameter = load(*program_counter + 2, &memory, s2)?;
-            let destination =
-                load(*program_counter + 3, &memory, AddressingMode::Immediate)? as usize;
-            memory[destination] = if left_parameter < right_parameter {
-                1
-            } else {
-                0
-            };
-            *program_counter = *program_counter + 4;
-        }
-        Some(Operation::Equals {
-            left_parameter_mode: s1,
-            right_parameter_mode: s2,
-        }) => {
-            let left_parameter = load(*program_counter + 1, &memory, s1)?;
-            let right_parameter = load(*program_counter + 2, &memory, s2)?;
-            let destination =
-                load(*program_counter + 3, &memory, AddressingMode::Immediate)? as usize;
-            memory[destination] = if left_parameter == right_parameter {
-                1
-            } else {
-                0
-            };
-            *program_counter = *program_counter + 4;
-        }
-        Some(Operation::Input) => {
-            let pos =
-                load(*program_counter + 1, &memory, AddressingMode::Immediate)?.clone() as usize;
-            let arg = input
-                .pop_front()
-                .ok_or(IoError::new(ErrorKind::Other, "insufficient inputs"))?;
-            memory[pos] = arg;
-            *program_counter = *program_counter + 2;
-        }
-        Some(Operation::Print) => {
-            let pos =
-                load(*program_counter + 1, &memory, AddressingMode::Immediate)?.clone() as usize;
-            *program_counter = *program_counter + 2;
-            return Ok(OperationResult::Output(memory[pos]));
-        }
-        Some(Operation::Exit) => return Ok(OperationResult::Finished),
-        _ => {
-            return Err(IoError::new(
-                ErrorKind::Other,
-                format!(
-                    "invalid opcode: {:?} at pc {}",
-                    memory.get(*program_counter),
-                    *program_counter
-                ),
-            ))
+    #[test]
+    fn test_best_phases_skips_runaways() {
+        // adds the phase to the signal, but spins forever on a signal of 1
+        let program = parse_program("3,30,3,31,1008,31,1,32,1005,32,8,1,30,31,31,4,31,99");
+        let limits = Limits {
+            steps: Some(1000),
+            time: None,
+        };
+        for &threads in &[false, true] {
+            let search = best_phases(&program, &[1, 2, 4], Mode::Series, 2, threads, limits);
+            assert_eq!(search.best.map(|(_, signal)| signal), Some(7));
+            assert_eq!(search.runaway, vec![vec![1, 2, 4], vec![1, 4, 2]]);
+            assert!(search.failed.is_empty());
         }
-    };
-    Ok(OperationResult::Continue)
-}
-
-fn parse_opcode(opcode: i64) -> Option<Operation> {
-    let operation_str = opcode % 100;
-    let op1_mode_str = (opcode / 100) % 10;
-    let op2_mode_str = (opcode / 1000) % 10;
-
-    match operation_str {
-        1 => Some(Operation::Addition {
-            summand1_mode: mode_str_to_addressing_mode(op1_mode_str)?,
-            summand2_mode: mode_str_to_addressing_mode(op2_mode_str)?,
-        }),
-        2 => Some(Operation::Multiplication {
-            factor1_mode: mode_str_to_addressing_mode(op1_mode_str)?,
-            factor2_mode: mode_str_to_addressing_mode(op2_mode_str)?,
-        }),
-        3 => Some(Operation::Input),
-        4 => Some(Operation::Print),
-        5 => Some(Operation::JumpIfTrue {
-            condition_mode: mode_str_to_addressing_mode(op1_mode_str)?,
-            value_mode: mode_str_to_addressing_mode(op2_mode_str)?,
-        }),
-        6 => Some(Operation::JumpIfFalse {
-            condition_mode: mode_str_to_addressing_mode(op1_mode_str)?,
-            value_mode: mode_str_to_addressing_mode(op2_mode_str)?,
-        }),
-        7 => Some(Operation::LessThan {
-            left_parameter_mode: mode_str_to_addressing_mode(op1_mode_str)?,
-            right_parameter_mode: mode_str_to_addressing_mode(op2_mode_str)?,
-        }),
-        8 => Some(Operation::Equals {
-            left_parameter_mode: mode_str_to_addressing_mode(op1_mode_str)?,
-            right_parameter_mode: mode_str_to_addressing_mode(op2_mode_str)?,
-        }),
-        99 => Some(Operation::Exit),
-        _ => None,
     }
-}
-
-fn mode_str_to_addressing_mode(c: i64) -> Option<AddressingMode> {
-    match c {
-        0 => Some(AddressingMode::Indirect),
-        1 => Some(AddressingMode::Immediate),
-        _ => None,
-    }
-}
-
-#[derive(PartialEq, Eq, Debug)]
-enum Operation {
-    Addition {
-        summand1_mode: AddressingMode,
-        summand2_mode: AddressingMode,
-    },
-    Multiplication {
-        factor1_mode: AddressingMode,
-        factor2_mode: AddressingMode,
-    },
-    Input,
-    Print,
-    JumpIfTrue {
-        condition_mode: AddressingMode,
-        value_mode: AddressingMode,
-    },
-    JumpIfFalse {
-        condition_mode: AddressingMode,
-        value_mode: AddressingMode,
-    },
-    LessThan {
-        left_parameter_mode: AddressingMode,
-        right_parameter_mode: AddressingMode,
-    },
-    Equals {
-        left_parameter_mode: AddressingMode,
-        right_parameter_mode: AddressingMode,
-    },
-    Exit,
-}
-
-#[derive(Copy, Clone, PartialEq, Eq, Debug)]
-enum AddressingMode {
-    Indirect,
-    Immediate,
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
 
     #[test]
-    fn test_parse_opcode_example1() {
+    fn test_best_phases_skips_silent_amplifiers() {
+        // adds the phase to the signal, but halts without output for phase 1
+        let program = parse_program("3,30,3,31,1008,30,1,32,1005,32,17,1,30,31,31,4,31,99");
+        let search = best_phases(&program, &[1, 2], Mode::Series, 1, false, Limits::default());
+        assert_eq!(search.best, None);
         assert_eq!(
-            parse_opcode(1002),
-            Some(Operation::Multiplication {
-                factor1_mode: AddressingMode::Indirect,
-                factor2_mode: AddressingMode::Immediate
-            })
+            search.failed,
+            vec![
+                (vec![1, 2], "amplifiers stuck waiting for input".to_string()),
+                (vec![2, 1], "halted without a signal".to_string()),
+            ]
         );
+        let threaded = best_phases(&program, &[1, 2], Mode::Series, 2, true, Limits::default());
+        assert_eq!(threaded, search);
+    }
+
+    #[test]
+    fn test_best_phases_keeps_early_output() {
+        // prints 7 before reading the signal, then echoes the signal
+        let program = parse_program("3,20,104,7,3,20,4,20,99");
+        let search = best_phases(&program, &[0, 1], Mode::Series, 1, false, Limits::default());
+        assert_eq!(search.best, Some((vec![0, 1], 7)));
     }
 }
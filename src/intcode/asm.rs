@@ -0,0 +1,323 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+use super::{parse_opcode, AddressingMode, Operation};
+
+const OPCODES: [i64; 10] = [1, 2, 3, 4, 5, 6, 7, 8, 9, 99];
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct AsmError {
+    pub line: usize,
+    pub message: String,
+}
+
+enum Item {
+    Instruction {
+        opcode: i64,
+        operands: Vec<(AddressingMode, String)>,
+    },
+    Data(Vec<String>),
+}
+
+struct SourceLine {
+    number: usize,
+    address: usize,
+    item: Item,
+}
+
+/// Assembles mnemonic source into a program.
+///
+/// Every line holds an optional `label:`, then either an instruction such as
+/// `ADD [x], #3, [rb-1]` or a `DB` directive listing data words. Operands and
+/// data words are numbers, labels or sums of both (`table+2`), and `;` starts a
+/// comment. A numeric label like `0004:` asserts the current address, so
+/// disassembler listings including their raw word column assemble unchanged.
+pub fn assemble(source: &str) -> Result<Vec<i64>, AsmError> {
+    let mut labels = HashMap::new();
+    let mut lines = vec![];
+    let mut address = 0;
+
+    for (index, text) in source.lines().enumerate() {
+        let number = index + 1;
+        let error = |message: String| AsmError {
+            line: number,
+            message,
+        };
+        let mut rest = text.split(';').next().unwrap_or("").trim();
+
+        let mut listing_address = false;
+        while let Some((label, remainder)) = split_label(rest) {
+            if let Ok(expected) = label.parse::<usize>() {
+                if expected != address {
+                    return Err(error(format!(
+                        "listing address {} doesn't match assembled address {}",
+                        expected, address
+                    )));
+                }
+                listing_address = true;
+            } else if labels.insert(label.to_string(), address).is_some() {
+                return Err(error(format!("duplicate label {}", label)));
+            }
+            rest = remainder;
+        }
+        if listing_address && rest.starts_with(|c: char| c.is_ascii_digit() || c == '-') {
+            rest = rest
+                .split_once(char::is_whitespace)
+                .map_or("", |(_, remainder)| remainder)
+                .trim();
+        }
+        if rest.is_empty() {
+            continue;
+        }
+
+        let (mnemonic, operands) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+        let operands: Vec<&str> = operands
+            .split(',')
+            .map(str::trim)
+            .filter(|operand| !operand.is_empty())
+            .collect();
+
+        let item = if mnemonic.eq_ignore_ascii_case("DB") {
+            Item::Data(operands.iter().map(|word| word.to_string()).collect())
+        } else {
+            let (opcode, operation) =
+                lookup(mnemonic).ok_or_else(|| error(format!("unknown mnemonic {}", mnemonic)))?;
+            let expected = operation.parameter_modes().len();
+            if operands.len() != expected {
+                return Err(error(format!(
+                    "{} takes {} operands, found {}",
+                    operation.mnemonic(),
+                    expected,
+                    operands.len()
+                )));
+            }
+            Item::Instruction {
+                opcode,
+                operands: operands
+                    .iter()
+                    .map(|operand| parse_operand(operand).map_err(&error))
+                    .collect::<Result<_, _>>()?,
+            }
+        };
+        let size = match &item {
+            Item::Instruction { operands, .. } => operands.len() + 1,
+            Item::Data(words) => words.len(),
+        };
+        lines.push(SourceLine {
+            number,
+            address,
+            item,
+        });
+        address += size;
+    }
+
+    let mut program = Vec::with_capacity(address);
+    for line in lines {
+        let number = line.number;
+        let error = |message: String| AsmError {
+            line: number,
+            message,
+        };
+        debug_assert_eq!(program.len(), line.address);
+        match line.item {
+            Item::Instruction { opcode, operands } => {
+                let modes = operands
+                    .iter()
+                    .rev()
+                    .fold(0, |modes, (mode, _)| modes * 10 + mode_digit(*mode));
+                program.push(modes * 100 + opcode);
+                for (_, expression) in operands {
+                    program.push(evaluate(&expression, &labels).map_err(&error)?);
+                }
+            }
+            Item::Data(words) => {
+                for word in words {
+                    program.push(evaluate(&word, &labels).map_err(&error)?);
+                }
+            }
+        }
+    }
+    Ok(program)
+}
+
+pub fn format_program(program: &[i64]) -> String {
+    program
+        .iter()
+        .map(|value| value.to_string())
+        .collect::<Vec<String>>()
+        .join(",")
+}
+
+fn lookup(mnemonic: &str) -> Option<(i64, Operation)> {
+    OPCODES
+        .iter()
+        .filter_map(|&opcode| parse_opcode(opcode).map(|operation| (opcode, operation)))
+        .find(|(_, operation)| operation.mnemonic().eq_ignore_ascii_case(mnemonic))
+}
+
+fn mode_digit(mode: AddressingMode) -> i64 {
+    match mode {
+        AddressingMode::Indirect => 0,
+        AddressingMode::Immediate => 1,
+        AddressingMode::Relative => 2,
+    }
+}
+
+fn split_label(text: &str) -> Option<(&str, &str)> {
+    let (label, rest) = text.split_once(':')?;
+    if !label.is_empty() && label.chars().all(|c| c.is_alphanumeric() || c == '_') {
+        Some((label, rest.trim()))
+    } else {
+        None
+    }
+}
+
+fn parse_operand(operand: &str) -> Result<(AddressingMode, String), String> {
+    if let Some(expression) = operand.strip_prefix('#') {
+        return Ok((AddressingMode::Immediate, expression.trim().to_string()));
+    }
+    let inner = operand
+        .strip_prefix('[')
+        .and_then(|operand| operand.strip_suffix(']'))
+        .ok_or_else(|| format!("invalid operand {}", operand))?
+        .trim();
+    match inner.strip_prefix("rb") {
+        Some("") => Ok((AddressingMode::Relative, "0".to_string())),
+        Some(offset) if offset.trim_start().starts_with(['+', '-']) => {
+            Ok((AddressingMode::Relative, format!("0{}", offset.trim())))
+        }
+        _ => Ok((AddressingMode::Indirect, inner.to_string())),
+    }
+}
+
+/// Evaluates sums and differences of numbers and labels.
+fn evaluate(expression: &str, labels: &HashMap<String, usize>) -> Result<i64, String> {
+    let mut value = 0;
+    let mut sign = 1;
+    let mut term = String::new();
+    let mut terms = vec![];
+    for c in expression.chars().filter(|c| !c.is_whitespace()) {
+        match c {
+            '+' | '-' => {
+                if !term.is_empty() {
+                    terms.push((sign, term.clone()));
+                    term.clear();
+                    sign = 1;
+                }
+                if c == '-' {
+                    sign = -sign;
+                }
+            }
+            _ => term.push(c),
+        }
+    }
+    if term.is_empty() {
+        return Err(format!("invalid expression {}", expression));
+    }
+    terms.push((sign, term));
+
+    let overflow = || format!("{} overflows", expression);
+    for (sign, term) in terms {
+        // the sign belongs to a literal, or i64::MIN couldn't be written
+        let literal = if sign < 0 {
+            format!("-{}", term)
+        } else {
+            term.clone()
+        };
+        let term_value = match literal.parse::<i64>() {
+            Ok(number) => number,
+            Err(_) if term.chars().all(|c| c.is_ascii_digit()) => {
+                return Err(format!("number {} out of range", literal))
+            }
+            Err(_) => {
+                let address = *labels
+                    .get(&term)
+                    .ok_or_else(|| format!("unknown label {}", term))?;
+                (address as i64).checked_mul(sign).ok_or_else(overflow)?
+            }
+        };
+        value = term_value.checked_add(value).ok_or_else(overflow)?;
+    }
+    Ok(value)
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl Error for AsmError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::disasm::disassemble;
+    use crate::intcode::parse_program;
+
+    #[test]
+    fn test_assemble_labels_and_data() {
+        let source = "
+            ; prints the input doubled
+            start:  IN [value]
+                    MUL [value], #2, [value]
+                    out [value]
+                    JNZ #1, #end
+            value:  db 0
+            end:    HLT
+        ";
+        assert_eq!(
+            assemble(source),
+            Ok(parse_program("3,11,1002,11,2,11,4,11,1105,1,12,0,99"))
+        );
+    }
+
+    #[test]
+    fn test_assemble_relative_operands() {
+        assert_eq!(
+            assemble("ARB #-1\nADD [rb+2], [rb], [rb-3]\nDB data+1, -4\ndata: HLT"),
+            Ok(vec![109, -1, 22201, 2, 0, -3, 9, -4, 99])
+        );
+    }
+
+    #[test]
+    fn test_assemble_errors() {
+        assert_eq!(assemble("FOO #1").unwrap_err().line, 1);
+        assert_eq!(assemble("\nADD #1, #2").unwrap_err().line, 2);
+        assert_eq!(assemble("JZ #1, #nowhere").unwrap_err().line, 1);
+        assert_eq!(assemble("0000: HLT\n0002: HLT").unwrap_err().line, 2);
+        assert_eq!(
+            assemble("DB 9223372036854775807+1").unwrap_err().message,
+            "9223372036854775807+1 overflows"
+        );
+        assert_eq!(
+            assemble("DB 9223372036854775808").unwrap_err().message,
+            "number 9223372036854775808 out of range"
+        );
+    }
+
+    #[test]
+    fn test_assemble_extreme_numbers() {
+        assert_eq!(
+            assemble("DB -9223372036854775808, 9223372036854775807, -1-9223372036854775807"),
+            Ok(vec![i64::MIN, i64::MAX, i64::MIN])
+        );
+    }
+
+    #[test]
+    fn test_round_trip_disassembly() {
+        let program = parse_program(
+            "3,21,1008,21,8,20,1005,20,22,107,8,21,20,1006,20,31,1106,0,36,98,0,0,\
+             1002,21,125,20,4,20,1105,1,46,104,999,1105,1,46,1101,1000,1,20,4,20,\
+             1105,1,46,98,99,109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99,\
+             10004,5,100099,104,-9223372036854775808",
+        );
+        let listing = disassemble(&program)
+            .iter()
+            .map(|line| line.to_string())
+            .collect::<Vec<String>>()
+            .join("\n");
+        assert_eq!(assemble(&listing), Ok(program));
+    }
+}
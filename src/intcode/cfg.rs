@@ -0,0 +1,304 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+use super::disasm::{disassemble_at, Line};
+use super::{AddressingMode, Memory, Operation};
+
+/// How control can leave a basic block.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Edge {
+    /// Execution falls through to the block starting at the address.
+    Next(usize),
+    /// A jump to an immediate address.
+    Jump(usize),
+    /// The jump at `pc` takes its target from memory.
+    Indirect { pc: usize },
+    /// The instruction at `pc` overwrites code at `address`, so the graph
+    /// may not show what actually runs.
+    SelfModifying { pc: usize, address: usize },
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Block {
+    pub start: usize,
+    /// The block's instructions, or a single data line if the block starts
+    /// at a word that doesn't decode.
+    pub lines: Vec<Line>,
+    pub edges: Vec<Edge>,
+}
+
+/// Basic blocks reachable from address 0, keyed by start address.
+pub struct Graph {
+    pub blocks: BTreeMap<usize, Block>,
+}
+
+/// Whether a jump is taken, if its condition is an immediate value.
+pub fn jump_taken(operation: Operation, mode: AddressingMode, condition: i64) -> Option<bool> {
+    if mode != AddressingMode::Immediate {
+        return None;
+    }
+    Some((condition != 0) == matches!(operation, Operation::JumpIfTrue { .. }))
+}
+
+/// Where execution can go after `line`, ignoring self-modification.
+fn successors(line: &Line) -> Vec<Edge> {
+    let (address, operation, words) = match line {
+        Line::Instruction {
+            address,
+            operation,
+            words,
+        } => (*address, *operation, words),
+        Line::Data { .. } => return vec![],
+    };
+    let next = address + operation.len();
+    match operation {
+        Operation::Exit => vec![],
+        Operation::JumpIfTrue {
+            condition_mode,
+            value_mode,
+        }
+        | Operation::JumpIfFalse {
+            condition_mode,
+            value_mode,
+        } => {
+            let taken = jump_taken(operation, condition_mode, words[1]);
+            let mut edges = vec![];
+            if taken != Some(false) {
+                edges.push(match (value_mode, words[2]) {
+                    (AddressingMode::Immediate, target) if target >= 0 => {
+                        Edge::Jump(target as usize)
+                    }
+                    _ => Edge::Indirect { pc: address },
+                });
+            }
+            if taken != Some(true) {
+                edges.push(Edge::Next(next));
+            }
+            edges
+        }
+        _ => vec![Edge::Next(next)],
+    }
+}
+
+/// The fixed address `line` writes to, if any.
+fn written_address(line: &Line) -> Option<usize> {
+    let (operation, words) = match line {
+        Line::Instruction {
+            operation, words, ..
+        } => (operation, words),
+        Line::Data { .. } => return None,
+    };
+    let (mode, parameter) = match *operation {
+        Operation::Addition {
+            destination_mode, ..
+        }
+        | Operation::Multiplication {
+            destination_mode, ..
+        }
+        | Operation::LessThan {
+            destination_mode, ..
+        }
+        | Operation::Equals {
+            destination_mode, ..
+        } => (destination_mode, words[3]),
+        Operation::Input { destination_mode } => (destination_mode, words[1]),
+        _ => return None,
+    };
+    if mode == AddressingMode::Indirect && parameter >= 0 {
+        Some(parameter as usize)
+    } else {
+        None
+    }
+}
+
+/// Follows fall-through and immediate jump targets from address 0. Jumps
+/// start new blocks, as do their targets.
+pub fn build(program: &[i64]) -> Graph {
+    let memory = Memory::from(program.to_vec());
+    let mut lines = BTreeMap::new();
+    let mut leaders = BTreeSet::new();
+    leaders.insert(0);
+    let mut pending = vec![0];
+    while let Some(pc) = pending.pop() {
+        if lines.contains_key(&pc) {
+            continue;
+        }
+        let line = disassemble_at(&memory, pc);
+        let edges = successors(&line);
+        for &edge in &edges {
+            match edge {
+                Edge::Next(address) if edges.len() == 1 => pending.push(address),
+                Edge::Next(address) | Edge::Jump(address) => {
+                    leaders.insert(address);
+                    pending.push(address);
+                }
+                _ => (),
+            }
+        }
+        lines.insert(pc, line);
+    }
+
+    let code: BTreeSet<usize> = lines
+        .values()
+        .filter_map(|line| match line {
+            Line::Instruction { address, .. } => Some(*address..*address + line.size()),
+            Line::Data { .. } => None,
+        })
+        .flatten()
+        .collect();
+
+    let mut blocks = BTreeMap::new();
+    for &start in &leaders {
+        let mut block = Block {
+            start,
+            lines: vec![],
+            edges: vec![],
+        };
+        let mut pc = start;
+        loop {
+            let line = lines[&pc].clone();
+            if let Some(address) = written_address(&line).filter(|address| code.contains(address)) {
+                block.edges.push(Edge::SelfModifying { pc, address });
+            }
+            let edges = successors(&line);
+            block.lines.push(line);
+            match edges.as_slice() {
+                [Edge::Next(next)] if !leaders.contains(next) => pc = *next,
+                _ => {
+                    block.edges.extend(edges);
+                    break;
+                }
+            }
+        }
+        blocks.insert(start, block);
+    }
+    Graph { blocks }
+}
+
+impl Graph {
+    /// Renders the graph in Graphviz DOT format. Unknown edges lead to
+    /// dashed `?` nodes.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::new();
+        let out = &mut dot;
+        writeln!(out, "digraph intcode {{").unwrap();
+        writeln!(out, "    node [shape=box, fontname=monospace];").unwrap();
+        let mut unknown = 0;
+        for block in self.blocks.values() {
+            let label: String = block
+                .lines
+                .iter()
+                .map(|line| format!("{}\\l", line))
+                .collect();
+            writeln!(out, "    b{} [label=\"{}\"];", block.start, label).unwrap();
+            for edge in &block.edges {
+                match *edge {
+                    Edge::Next(address) => {
+                        writeln!(out, "    b{} -> b{};", block.start, address).unwrap()
+                    }
+                    Edge::Jump(address) => writeln!(
+                        out,
+                        "    b{} -> b{} [label=\"jump\"];",
+                        block.start, address
+                    )
+                    .unwrap(),
+                    Edge::Indirect { pc } | Edge::SelfModifying { pc, .. } => {
+                        let label = match *edge {
+                            Edge::SelfModifying { address, .. } => {
+                                format!("{:04} writes {:04}", pc, address)
+                            }
+                            _ => format!("{:04} jumps indirectly", pc),
+                        };
+                        writeln!(out, "    u{} [label=\"?\", style=dashed];", unknown).unwrap();
+                        writeln!(
+                            out,
+                            "    b{} -> u{} [label=\"{}\", style=dashed];",
+                            block.start, unknown, label
+                        )
+                        .unwrap();
+                        unknown += 1;
+                    }
+                }
+            }
+        }
+        writeln!(out, "}}").unwrap();
+        dot
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::parse_program;
+
+    #[test]
+    fn test_blocks_and_edges() {
+        // day5's "is the input 8" check using jumps
+        let graph = build(&parse_program(
+            "3,3,1108,-1,8,3,1005,3,14,104,0,1105,1,16,104,1,99",
+        ));
+        let summary: Vec<(usize, usize, Vec<Edge>)> = graph
+            .blocks
+            .values()
+            .map(|block| (block.start, block.lines.len(), block.edges.clone()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (
+                    0,
+                    3,
+                    vec![
+                        Edge::SelfModifying { pc: 0, address: 3 },
+                        Edge::SelfModifying { pc: 2, address: 3 },
+                        Edge::Jump(14),
+                        Edge::Next(9),
+                    ]
+                ),
+                (9, 2, vec![Edge::Jump(16)]),
+                (14, 1, vec![Edge::Next(16)]),
+                (16, 1, vec![]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_indirect_jump_and_data() {
+        let graph = build(&parse_program("1006,8,6,1105,1,42,6,0,0"));
+        let edges: Vec<&Vec<Edge>> = graph.blocks.values().map(|block| &block.edges).collect();
+        assert_eq!(
+            edges,
+            vec![
+                &vec![Edge::Jump(6), Edge::Next(3)],
+                &vec![Edge::Jump(42)],
+                &vec![Edge::Indirect { pc: 6 }, Edge::Next(9)],
+                &vec![],
+                &vec![],
+            ]
+        );
+        assert_eq!(
+            graph.blocks[&42].lines,
+            vec![Line::Data {
+                address: 42,
+                value: 0
+            }]
+        );
+    }
+
+    #[test]
+    fn test_dot_output() {
+        let graph = build(&parse_program("1005,0,5,104,7,99"));
+        assert_eq!(
+            graph.to_dot(),
+            "digraph intcode {\n    \
+             node [shape=box, fontname=monospace];\n    \
+             b0 [label=\"0000: 1005,0,5                 JNZ [0], #5\\l\"];\n    \
+             b0 -> b5 [label=\"jump\"];\n    \
+             b0 -> b3;\n    \
+             b3 [label=\"0003: 104,7                    OUT #7\\l\"];\n    \
+             b3 -> b5;\n    \
+             b5 [label=\"0005: 99                       HLT\\l\"];\n\
+             }\n"
+        );
+    }
+}
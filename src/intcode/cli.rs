@@ -0,0 +1,382 @@
+use std::collections::VecDeque;
+use std::fs::{self, File};
+use std::io::{self, stdin, stdout, BufReader, BufWriter, Read};
+use std::num::Wrapping;
+use std::process;
+use std::time::Instant;
+
+use clap::{App, Arg, ArgGroup, ArgMatches, SubCommand};
+use num_bigint::BigInt;
+
+use super::debugger::Debugger;
+use super::device::AsciiTerminal;
+use super::fast::CachedMachine;
+use super::trace::{self, BinaryTrace, TextTrace, Tracer};
+use super::word::{parse_words, Word};
+use super::{
+    asm, cfg, compile, disasm, parse_opcode, parse_program, IntcodeError, Machine, RunMode,
+};
+
+pub fn subcommand() -> App<'static, 'static> {
+    SubCommand::with_name("intcode")
+        .about("Tools for working with Intcode programs")
+        .subcommand(
+            SubCommand::with_name("asm")
+                .about("Assembles mnemonic source into a comma-separated program")
+                .arg(
+                    Arg::with_name("source")
+                        .help("Source file, read from stdin if omitted")
+                        .index(1),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("bench")
+                .about("Times the caching interpreter against the plain one")
+                .arg(program_arg())
+                .arg(input_arg())
+                .arg(
+                    Arg::with_name("runs")
+                        .long("runs")
+                        .takes_value(true)
+                        .default_value("1000")
+                        .help("Number of times to run the program on each interpreter"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("cfg")
+                .about("Prints the control-flow graph of a program in Graphviz DOT format")
+                .arg(program_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name("compile")
+                .about("Translates a program into Rust source")
+                .arg(program_arg())
+                .arg(
+                    Arg::with_name("name")
+                        .long("name")
+                        .takes_value(true)
+                        .default_value("Compiled")
+                        .help("Name of the generated struct"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("debug")
+                .about("Steps through a program interactively")
+                .arg(
+                    Arg::with_name("program")
+                        .help("File containing the program")
+                        .required(true)
+                        .index(1),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("disasm")
+                .about("Prints a disassembly listing of a program")
+                .arg(program_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name("replay")
+                .about("Reconstructs the machine state at a step of a recorded trace")
+                .arg(
+                    Arg::with_name("program")
+                        .help("File containing the traced program")
+                        .required(true)
+                        .index(1),
+                )
+                .arg(
+                    Arg::with_name("trace")
+                        .help("Text or binary trace file")
+                        .required(true)
+                        .index(2),
+                )
+                .arg(
+                    Arg::with_name("step")
+                        .long("step")
+                        .takes_value(true)
+                        .help("Step to stop after, the end of the trace if omitted"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("run")
+                .about("Runs a program to completion and prints its output")
+                .arg(program_arg())
+                .arg(input_arg())
+                .arg(
+                    Arg::with_name("word")
+                        .long("word")
+                        .takes_value(true)
+                        .possible_values(&["checked", "wrapping", "i128", "big"])
+                        .default_value("checked")
+                        .help("Integer type of memory cells and what happens on overflow"),
+                )
+                .arg(
+                    Arg::with_name("ascii")
+                        .long("ascii")
+                        .requires("program")
+                        .conflicts_with("input")
+                        .help("Talks to the program as a text terminal on stdin and stdout"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("trace")
+                .about("Runs a program to completion and records every instruction")
+                .arg(program_arg())
+                .arg(input_arg())
+                .arg(
+                    Arg::with_name("text")
+                        .long("text")
+                        .takes_value(true)
+                        .help("File to write a line-oriented trace to"),
+                )
+                .arg(
+                    Arg::with_name("binary")
+                        .long("binary")
+                        .takes_value(true)
+                        .help("File to write a binary trace to"),
+                )
+                .group(
+                    ArgGroup::with_name("format")
+                        .args(&["text", "binary"])
+                        .multiple(true)
+                        .required(true),
+                ),
+        )
+}
+
+pub fn run(matches: &ArgMatches) {
+    if let Some(matches) = matches.subcommand_matches("asm") {
+        let source = match matches.value_of("source") {
+            Some(path) => fs::read_to_string(path).expect("cannot read source"),
+            None => {
+                let mut source = String::new();
+                stdin()
+                    .read_to_string(&mut source)
+                    .expect("did not enter a correct string");
+                source
+            }
+        };
+        match asm::assemble(&source) {
+            Ok(program) => println!("{}", asm::format_program(&program)),
+            Err(e) => {
+                eprintln!("{}", e);
+                process::exit(1);
+            }
+        }
+    }
+    if let Some(matches) = matches.subcommand_matches("bench") {
+        bench(matches);
+    }
+    if let Some(matches) = matches.subcommand_matches("cfg") {
+        print!("{}", cfg::build(&load_program(matches)).to_dot());
+    }
+    if let Some(matches) = matches.subcommand_matches("compile") {
+        let name = matches.value_of("name").unwrap();
+        print!("{}", compile::compile(&load_program(matches), name));
+    }
+    if let Some(matches) = matches.subcommand_matches("debug") {
+        Debugger::new(load_program(matches))
+            .repl()
+            .expect("failed to talk to terminal");
+    }
+    if let Some(matches) = matches.subcommand_matches("disasm") {
+        for line in disasm::disassemble(&load_program(matches)) {
+            println!("{}", line);
+        }
+    }
+    if let Some(matches) = matches.subcommand_matches("replay") {
+        replay(matches);
+    }
+    if let Some(matches) = matches.subcommand_matches("run") {
+        if matches.is_present("ascii") {
+            run_ascii(matches);
+            return;
+        }
+        match matches.value_of("word").unwrap() {
+            "wrapping" => run_words::<Wrapping<i64>>(matches),
+            "i128" => run_words::<i128>(matches),
+            "big" => run_words::<BigInt>(matches),
+            _ => run_words::<i64>(matches),
+        }
+    }
+    if let Some(matches) = matches.subcommand_matches("trace") {
+        record_trace(matches);
+    }
+}
+
+fn run_words<W: Word>(matches: &ArgMatches) {
+    let input: VecDeque<W> = parse_words(matches.value_of("input").unwrap_or_default()).into();
+    let program = parse_words(&load_source(matches));
+    let mut machine = Machine::with_devices(program, input, Vec::<W>::new());
+    let result = machine.run(RunMode::ToHalt);
+    for value in machine.output() {
+        println!("{}", value);
+    }
+    if let Err(e) = result {
+        fail(e);
+    }
+}
+
+fn run_ascii(matches: &ArgMatches) {
+    let mut machine = Machine::with_devices(
+        load_program(matches),
+        AsciiTerminal::stdio(),
+        AsciiTerminal::new(io::empty(), stdout()),
+    );
+    if let Err(e) = machine.run(RunMode::ToHalt) {
+        fail(e);
+    }
+}
+
+/// Reports a failed run, naming the operation at fault if it decodes, and
+/// exits.
+fn fail(e: IntcodeError) -> ! {
+    match parse_opcode(e.instruction()) {
+        Some(operation) => eprintln!("{} ({})", e, operation.mnemonic()),
+        None => eprintln!("{}", e),
+    }
+    process::exit(1);
+}
+
+fn bench(matches: &ArgMatches) {
+    let program = load_program(matches);
+    let input = load_input(matches);
+    let runs: u32 = matches
+        .value_of("runs")
+        .unwrap()
+        .parse()
+        .expect("runs must be a number");
+
+    let start = Instant::now();
+    let mut naive = None;
+    for _ in 0..runs {
+        let mut machine = Machine::with_devices(program.clone(), input.clone(), vec![]);
+        let result = machine.run(RunMode::ToHalt);
+        naive = Some((result, machine.steps(), machine.output().clone()));
+    }
+    let naive_time = start.elapsed();
+
+    let start = Instant::now();
+    let mut cached = None;
+    for _ in 0..runs {
+        let mut machine = CachedMachine::with_devices(program.clone(), input.clone(), vec![]);
+        let result = machine.run(RunMode::ToHalt);
+        cached = Some((result, machine.steps(), machine.output().clone()));
+    }
+    let cached_time = start.elapsed();
+
+    if naive != cached {
+        eprintln!("interpreters disagree: {:?} vs {:?}", naive, cached);
+        process::exit(1);
+    }
+    if let Some((result, steps, _)) = naive {
+        println!("{} runs of {} instructions, {:?}", runs, steps, result);
+    }
+    println!("naive   {:?}", naive_time);
+    println!(
+        "cached  {:?} ({:.1}x)",
+        cached_time,
+        naive_time.as_secs_f64() / cached_time.as_secs_f64()
+    );
+}
+
+fn record_trace(matches: &ArgMatches) {
+    let mut machine = Machine::with_devices(load_program(matches), load_input(matches), vec![]);
+    // keep the profiler, if any, next to the requested traces
+    let mut tracers: Vec<Box<dyn Tracer>> = machine.take_tracer().into_iter().collect();
+    if let Some(path) = matches.value_of("text") {
+        let file = File::create(path).expect("cannot create trace");
+        tracers.push(Box::new(TextTrace::new(BufWriter::new(file))));
+    }
+    if let Some(path) = matches.value_of("binary") {
+        let file = File::create(path).expect("cannot create trace");
+        tracers.push(Box::new(BinaryTrace::new(BufWriter::new(file))));
+    }
+    machine.set_tracer(Box::new(tracers));
+
+    let result = machine.run(RunMode::ToHalt);
+    for value in machine.output() {
+        println!("{}", value);
+    }
+    if let Some(mut tracer) = machine.take_tracer() {
+        tracer.finish().expect("failed to write trace");
+    }
+    if let Err(e) = result {
+        fail(e);
+    }
+}
+
+fn replay(matches: &ArgMatches) {
+    let program = load_program(matches);
+    let file = File::open(matches.value_of("trace").unwrap()).expect("cannot open trace");
+    let steps = trace::read_trace(BufReader::new(file)).expect("cannot read trace");
+    let until = match matches.value_of("step") {
+        Some(step) => step.parse().expect("step must be a number"),
+        None => steps.len() as u64,
+    };
+
+    match trace::replay(program.clone(), &steps, until) {
+        Ok(state) => {
+            println!(
+                "step {}  pc {:04}  rb {}",
+                until.min(steps.len() as u64),
+                state.program_counter,
+                state.relative_base
+            );
+            let mut written: Vec<usize> = steps
+                .iter()
+                .take(until as usize)
+                .filter_map(|step| step.write.map(|write| write.address))
+                .collect();
+            written.sort_unstable();
+            written.dedup();
+            for address in written {
+                let initial = program.get(address).copied().unwrap_or(0);
+                if state.memory[address] != initial {
+                    println!("{:04}: {} -> {}", address, initial, state.memory[address]);
+                }
+            }
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    }
+}
+
+fn program_arg() -> Arg<'static, 'static> {
+    Arg::with_name("program")
+        .help("File containing the program, read from stdin if omitted")
+        .index(1)
+}
+
+fn input_arg() -> Arg<'static, 'static> {
+    Arg::with_name("input")
+        .long("input")
+        .takes_value(true)
+        .allow_hyphen_values(true)
+        .help("Comma-separated values to feed to the program")
+}
+
+fn load_input(matches: &ArgMatches) -> VecDeque<i64> {
+    matches
+        .value_of("input")
+        .map(|input| parse_program(input).into())
+        .unwrap_or_default()
+}
+
+fn load_source(matches: &ArgMatches) -> String {
+    match matches.value_of("program") {
+        Some(path) => fs::read_to_string(path).expect("cannot read program"),
+        None => {
+            let mut source = String::new();
+            stdin()
+                .read_to_string(&mut source)
+                .expect("did not enter a correct string");
+            source
+        }
+    }
+}
+
+fn load_program(matches: &ArgMatches) -> Vec<i64> {
+    parse_program(&load_source(matches))
+}
@@ -0,0 +1,724 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+use super::cfg::jump_taken;
+use super::disasm::disassemble_at;
+use super::{parse_opcode, AddressingMode, Memory, Operation};
+
+/// Instructions reachable from address 0, as far as they can be found
+/// without running the program.
+struct Analysis {
+    instructions: BTreeMap<usize, Operation>,
+    /// Addresses the generated code can be entered at.
+    entries: BTreeSet<usize>,
+    /// Every word belonging to a reachable instruction.
+    code: BTreeSet<usize>,
+}
+
+fn analyse(program: &[i64]) -> Analysis {
+    let read = |address: usize| program.get(address).copied().unwrap_or(0);
+    let mut analysis = Analysis {
+        instructions: BTreeMap::new(),
+        entries: BTreeSet::new(),
+        code: BTreeSet::new(),
+    };
+    analysis.entries.insert(0);
+    let mut pending = vec![0];
+    while let Some(pc) = pending.pop() {
+        if analysis.instructions.contains_key(&pc) {
+            continue;
+        }
+        let operation = match parse_opcode(read(pc)) {
+            Some(operation) => operation,
+            None => continue,
+        };
+        analysis.instructions.insert(pc, operation);
+        analysis.code.extend(pc..pc + operation.len());
+        let next = pc + operation.len();
+        match operation {
+            Operation::Exit => (),
+            Operation::JumpIfTrue {
+                condition_mode,
+                value_mode,
+            }
+            | Operation::JumpIfFalse {
+                condition_mode,
+                value_mode,
+            } => {
+                let taken = jump_taken(operation, condition_mode, read(pc + 1));
+                let target = read(pc + 2);
+                if value_mode == AddressingMode::Immediate && target >= 0 && taken != Some(false) {
+                    analysis.entries.insert(target as usize);
+                    pending.push(target as usize);
+                }
+                if taken != Some(true) {
+                    analysis.entries.insert(next);
+                    pending.push(next);
+                }
+            }
+            Operation::Input { .. } => {
+                // a machine waiting for input resumes at the instruction itself
+                analysis.entries.insert(pc);
+                pending.push(next);
+            }
+            Operation::Print { .. } => {
+                analysis.entries.insert(next);
+                pending.push(next);
+            }
+            _ => pending.push(next),
+        }
+    }
+    analysis
+}
+
+/// Translates `program` into Rust source defining a struct called `name`.
+///
+/// The struct runs the program's reachable instructions as native code on
+/// top of a `CachedMachine`, offers the same `run`/`push_input`/`output`
+/// methods as `Machine` and can be wired into a `Network`. Anything the generated code can't handle natively,
+/// such as a halt, a failing instruction or input that isn't there yet, is
+/// passed to the interpreter one instruction at a time, and so is every
+/// instruction once the machine's limits are reached. Once the program
+/// writes to its own code, the interpreter takes over for good.
+pub fn compile(program: &[i64], name: &str) -> String {
+    let analysis = analyse(program);
+    let memory = Memory::from(program.to_vec());
+    let mut source = String::new();
+    let out = &mut source;
+
+    writeln!(out, "// Generated by `intcode compile`, do not edit.").unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "use std::collections::VecDeque;").unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "use crate::intcode::fast::CachedMachine;").unwrap();
+    writeln!(out, "use crate::intcode::limit::Limits;").unwrap();
+    writeln!(out, "use crate::intcode::network::Node;").unwrap();
+    writeln!(out, "use crate::intcode::{{").unwrap();
+    writeln!(
+        out,
+        "    InputDevice, IntcodeError, OperationResult, OutputDevice, ProgramResult, RunMode,"
+    )
+    .unwrap();
+    writeln!(out, "}};").unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "const PROGRAM: [i64; {}] = [", program.len()).unwrap();
+    for row in program.chunks(12) {
+        let words: Vec<String> = row.iter().map(|word| word.to_string()).collect();
+        writeln!(out, "    {},", words.join(", ")).unwrap();
+    }
+    writeln!(out, "];").unwrap();
+    writeln!(out).unwrap();
+
+    let ranges = ranges(&analysis.code);
+    if ranges.is_empty() {
+        writeln!(out, "fn is_code(_address: usize) -> bool {{\n    false\n}}").unwrap();
+    } else {
+        writeln!(out, "fn is_code(address: usize) -> bool {{").unwrap();
+        writeln!(out, "    matches!(address, {})", ranges.join(" | ")).unwrap();
+        writeln!(out, "}}").unwrap();
+    }
+    writeln!(out).unwrap();
+
+    write!(
+        out,
+        "\
+pub struct {name}<I = VecDeque<i64>, O = Vec<i64>> {{
+    machine: CachedMachine<I, O>,
+    native: bool,
+}}
+
+impl {name} {{
+    pub fn new() -> {name} {{
+        {name}::with_devices(VecDeque::new(), vec![])
+    }}
+}}
+
+impl Default for {name} {{
+    fn default() -> {name} {{
+        {name}::new()
+    }}
+}}
+
+impl<O> {name}<VecDeque<i64>, O> {{
+    pub fn push_input(&mut self, value: i64) {{
+        self.machine.push_input(value);
+    }}
+}}
+
+impl<I: InputDevice, O: OutputDevice> {name}<I, O> {{
+    pub fn with_devices(input: I, output: O) -> {name}<I, O> {{
+        {name} {{
+            machine: CachedMachine::with_devices(PROGRAM.to_vec(), input, output),
+            native: true,
+        }}
+    }}
+
+    pub fn machine(&self) -> &CachedMachine<I, O> {{
+        &self.machine
+    }}
+
+    pub fn input_mut(&mut self) -> &mut I {{
+        self.machine.input_mut()
+    }}
+
+    pub fn output(&self) -> &O {{
+        self.machine.output()
+    }}
+
+    pub fn output_mut(&mut self) -> &mut O {{
+        self.machine.output_mut()
+    }}
+
+    pub fn is_halted(&self) -> bool {{
+        self.machine.is_halted()
+    }}
+
+    /// Same contract as `CachedMachine::set_limits`.
+    pub fn set_limits(&mut self, limits: Limits) {{
+        self.machine.set_limits(limits);
+    }}
+
+    /// Whether execution still uses the compiled code.
+    pub fn is_native(&self) -> bool {{
+        self.native
+    }}
+
+    /// Writes to memory. Changing the program's code hands execution to the
+    /// interpreter.
+    pub fn poke(&mut self, address: usize, value: i64) {{
+        if is_code(address) {{
+            self.native = false;
+        }}
+        self.machine.write(address, value);
+    }}
+
+    /// Same contract as `Machine::run`.
+    pub fn run(&mut self, mode: RunMode) -> Result<ProgramResult, IntcodeError> {{
+        loop {{
+            if !self.native {{
+                return self.machine.run(mode);
+            }}
+            match self.advance()? {{
+                OperationResult::Output(value) if mode == RunMode::UntilOutput => {{
+                    return Ok(ProgramResult::Output(value))
+                }}
+                OperationResult::NeedsInput if mode == RunMode::UntilInput => {{
+                    return Ok(ProgramResult::NeedsInput)
+                }}
+                OperationResult::NeedsInput => {{
+                    let pc = self.machine.program_counter();
+                    return Err(IntcodeError::InputExhausted {{
+                        pc,
+                        instruction: self.machine.read(pc),
+                    }});
+                }}
+                OperationResult::Finished => return Ok(ProgramResult::Finished),
+                _ => (),
+            }}
+        }}
+    }}
+
+    /// Runs compiled code up to the next output, backward jump or hand-over,
+    /// or else a single instruction on the interpreter.
+    pub fn advance(&mut self) -> Result<OperationResult, IntcodeError> {{
+        if !self.native {{
+            return self.machine.perform_operation();
+        }}
+        match self.execute() {{
+            Some(result) => Ok(result),
+            None => self.machine.perform_operation(),
+        }}
+    }}
+
+    /// Runs compiled code from the current pc. `None` leaves the instruction
+    /// at the pc to the interpreter.
+    #[allow(clippy::never_loop)] // unless a block leads into a later one
+    fn execute(&mut self) -> Option<OperationResult> {{
+        let m = &mut self.machine;
+        loop {{
+            match m.program_counter() {{
+",
+        name = name
+    )
+    .unwrap();
+
+    for &entry in &analysis.entries {
+        writeln!(out, "                {} => {{", entry).unwrap();
+        let mut pc = entry;
+        loop {
+            if pc != entry && analysis.entries.contains(&pc) {
+                emit(out, &format!("m.set_program_counter({});", pc));
+                break;
+            }
+            let operation = match analysis.instructions.get(&pc) {
+                Some(operation) => *operation,
+                None => {
+                    emit(out, &defer(pc));
+                    break;
+                }
+            };
+            emit(out, &format!("// {}", disassemble_at(&memory, pc)));
+            emit(
+                out,
+                &format!(
+                    "if m.limit_reached() {{\n    {}\n}}",
+                    defer(pc).replace('\n', "\n    ")
+                ),
+            );
+            let parameters: Vec<i64> = (1..operation.len())
+                .map(|offset| memory.read(pc + offset))
+                .collect();
+            match instruction(pc, operation, &parameters, &analysis.code) {
+                Some((lines, Flow::Next)) => {
+                    for line in lines {
+                        emit(out, &line);
+                    }
+                }
+                Some((lines, Flow::Leave)) => {
+                    for line in lines {
+                        emit(out, &line);
+                    }
+                    break;
+                }
+                None => {
+                    emit(out, &defer(pc));
+                    break;
+                }
+            }
+            pc += operation.len();
+        }
+        writeln!(out, "                }}").unwrap();
+    }
+
+    write!(
+        out,
+        "                _ => return None,
+            }}
+        }}
+    }}
+}}
+
+impl Node for {name} {{
+    fn advance(&mut self) -> Result<OperationResult, IntcodeError> {{
+        {name}::advance(self)
+    }}
+
+    fn push_input(&mut self, value: i64) {{
+        {name}::push_input(self, value);
+    }}
+
+    fn take_output(&mut self) -> Vec<i64> {{
+        std::mem::take(self.output_mut())
+    }}
+
+    fn is_halted(&self) -> bool {{
+        {name}::is_halted(self)
+    }}
+
+    fn steps(&self) -> u64 {{
+        self.machine.steps()
+    }}
+}}
+",
+        name = name
+    )
+    .unwrap();
+    source
+}
+
+fn emit(out: &mut String, code: &str) {
+    for line in code.lines() {
+        writeln!(out, "                    {}", line).unwrap();
+    }
+}
+
+fn defer(pc: usize) -> String {
+    format!("m.set_program_counter({});\nreturn None;", pc)
+}
+
+/// Whether an instruction's code lets execution carry on with the next one.
+enum Flow {
+    Next,
+    Leave,
+}
+
+/// Native code for one instruction, or `None` when it is always left to the
+/// interpreter.
+fn instruction(
+    pc: usize,
+    operation: Operation,
+    parameters: &[i64],
+    code: &BTreeSet<usize>,
+) -> Option<(Vec<String>, Flow)> {
+    let modes = operation.parameter_modes();
+    let next = pc + operation.len();
+    let mut lines = vec![];
+    let load = |lines: &mut Vec<String>, index: usize, name: &str| -> Option<()> {
+        let parameter = parameters[index];
+        lines.push(match modes[index] {
+            AddressingMode::Immediate => format!("let {} = {};", name, parameter),
+            AddressingMode::Indirect if parameter < 0 => return None,
+            AddressingMode::Indirect => format!("let {} = m.read({});", name, parameter),
+            AddressingMode::Relative => format!(
+                "let {} = match m.relative({}) {{\n    Some(address) => m.read(address),\n    None => {{\n        {}\n    }}\n}};",
+                name,
+                parameter,
+                defer(pc).replace('\n', "\n    ")
+            ),
+        });
+        Some(())
+    };
+    // Resolves the destination and returns the check for self-modification.
+    let destination = |lines: &mut Vec<String>, index: usize| -> Option<String> {
+        let parameter = parameters[index];
+        let check = match modes[index] {
+            AddressingMode::Immediate => return None,
+            AddressingMode::Indirect if parameter < 0 => return None,
+            AddressingMode::Indirect => {
+                lines.push(format!("let d = {};", parameter));
+                if code.contains(&(parameter as usize)) {
+                    "true"
+                } else {
+                    "false"
+                }
+            }
+            AddressingMode::Relative => {
+                lines.push(format!(
+                    "let d = match m.relative({}) {{\n    Some(address) => address,\n    None => {{\n        {}\n    }}\n}};",
+                    parameter,
+                    defer(pc).replace('\n', "\n    ")
+                ));
+                "is_code(d)"
+            }
+        };
+        Some(check.to_string())
+    };
+    let finish_write = |lines: &mut Vec<String>, check: String| -> Flow {
+        lines.push("m.count_step();".to_string());
+        let leave = format!(
+            "self.native = false;\nm.set_program_counter({});\nreturn Some(OperationResult::Continue);",
+            next
+        );
+        match check.as_str() {
+            "false" => Flow::Next,
+            "true" => {
+                lines.push(leave);
+                Flow::Leave
+            }
+            _ => {
+                lines.push(format!(
+                    "if {} {{\n    {}\n}}",
+                    check,
+                    leave.replace('\n', "\n    ")
+                ));
+                Flow::Next
+            }
+        }
+    };
+
+    let flow = match operation {
+        Operation::Addition { .. }
+        | Operation::Multiplication { .. }
+        | Operation::LessThan { .. }
+        | Operation::Equals { .. } => {
+            load(&mut lines, 0, "a")?;
+            load(&mut lines, 1, "b")?;
+            let check = destination(&mut lines, 2)?;
+            let overflowing = |method: &str| {
+                format!(
+                    "match i64::{}(a, b) {{\n    Some(value) => m.write(d, value),\n    None => {{\n        {}\n    }}\n}}",
+                    method,
+                    defer(pc).replace('\n', "\n        ")
+                )
+            };
+            lines.push(match operation {
+                Operation::Addition { .. } => overflowing("checked_add"),
+                Operation::Multiplication { .. } => overflowing("checked_mul"),
+                Operation::LessThan { .. } => "m.write(d, (a < b) as i64);".to_string(),
+                _ => "m.write(d, (a == b) as i64);".to_string(),
+            });
+            finish_write(&mut lines, check)
+        }
+        Operation::Input { .. } => {
+            let check = destination(&mut lines, 0)?;
+            lines.push(format!(
+                "let value = match m.input_mut().read() {{\n    Some(value) => value,\n    None => {{\n        {}\n    }}\n}};",
+                defer(pc).replace('\n', "\n    ")
+            ));
+            lines.push("m.write(d, value);".to_string());
+            finish_write(&mut lines, check)
+        }
+        Operation::Print { .. } => {
+            load(&mut lines, 0, "a")?;
+            lines.push("m.output_mut().write(a);".to_string());
+            lines.push("m.count_step();".to_string());
+            lines.push(format!("m.set_program_counter({});", next));
+            lines.push("return Some(OperationResult::Output(a));".to_string());
+            Flow::Leave
+        }
+        Operation::JumpIfTrue {
+            condition_mode,
+            value_mode,
+        }
+        | Operation::JumpIfFalse {
+            condition_mode,
+            value_mode,
+        } => {
+            let taken = jump_taken(operation, condition_mode, parameters[0]);
+            if taken == Some(false) {
+                // the interpreters read the target even if they don't jump
+                if value_mode != AddressingMode::Immediate {
+                    load(&mut lines, 1, "_")?;
+                }
+                lines.push("m.count_step();".to_string());
+                return Some((lines, Flow::Next));
+            }
+            if taken.is_none() {
+                load(&mut lines, 0, "a")?;
+            }
+            let target = parameters[1];
+            let jump = if value_mode == AddressingMode::Immediate {
+                if target < 0 {
+                    return None;
+                }
+                // only forward jumps stay in `execute`, so every call ends
+                let then = if target as usize > pc {
+                    "continue;"
+                } else {
+                    "return Some(OperationResult::Continue);"
+                };
+                format!(
+                    "m.count_step();\nm.set_program_counter({});\n{}",
+                    target, then
+                )
+            } else {
+                load(&mut lines, 1, "b")?;
+                format!(
+                    "if b < 0 {{\n    {}\n}}\nm.count_step();\nm.set_program_counter(b as usize);\nreturn Some(OperationResult::Continue);",
+                    defer(pc).replace('\n', "\n    ")
+                )
+            };
+            if taken == Some(true) {
+                lines.push(jump);
+                return Some((lines, Flow::Leave));
+            }
+            let test = match operation {
+                Operation::JumpIfTrue { .. } => "a != 0",
+                _ => "a == 0",
+            };
+            lines.push(format!(
+                "if {} {{\n    {}\n}}",
+                test,
+                jump.replace('\n', "\n    ")
+            ));
+            lines.push("m.count_step();".to_string());
+            Flow::Next
+        }
+        Operation::AdjustRelativeBase { .. } => {
+            load(&mut lines, 0, "a")?;
+            lines.push(format!(
+                "match m.relative_base().checked_add(a) {{\n    Some(base) => m.set_relative_base(base),\n    None => {{\n        {}\n    }}\n}}",
+                defer(pc).replace('\n', "\n        ")
+            ));
+            lines.push("m.count_step();".to_string());
+            Flow::Next
+        }
+        Operation::Exit => return None,
+    };
+    Some((lines, flow))
+}
+
+/// Formats a set of addresses as inclusive range patterns.
+fn ranges(addresses: &BTreeSet<usize>) -> Vec<String> {
+    let mut ranges: Vec<(usize, usize)> = vec![];
+    for &address in addresses {
+        match ranges.last_mut() {
+            Some((_, end)) if *end + 1 == address => *end = address,
+            _ => ranges.push((address, address)),
+        }
+    }
+    ranges
+        .into_iter()
+        .map(|(start, end)| {
+            if start == end {
+                start.to_string()
+            } else {
+                format!("{}..={}", start, end)
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::fixtures::feedback_amplifier::FeedbackAmplifier;
+    use crate::intcode::fixtures::quine::Quine;
+    use crate::intcode::fixtures::self_modifying::SelfModifying;
+    use crate::intcode::fixtures::spinner::Spinner;
+    use crate::intcode::limit::{Limit, Limits};
+    use crate::intcode::{parse_program, IntcodeError, Machine, ProgramResult, RunMode};
+    use std::time::Duration;
+
+    // cargo run -- intcode compile --name FeedbackAmplifier
+    const FEEDBACK_AMPLIFIER: &str =
+        "3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,27,4,27,1001,28,-1,28,1005,28,6,99,0,0,5";
+    // cargo run -- intcode compile --name Quine
+    const QUINE: &str = "109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99";
+    // cargo run -- intcode compile --name SelfModifying
+    const SELF_MODIFYING: &str =
+        "1101,3,4,100,4,100,1005,101,20,1101,1,0,101,1101,1102,0,0,1105,1,0,99";
+    // cargo run -- intcode compile --name Spinner
+    const SPINNER: &str = "1105,1,0";
+
+    #[test]
+    fn test_fixtures_are_up_to_date() {
+        assert_eq!(
+            compile(&parse_program(FEEDBACK_AMPLIFIER), "FeedbackAmplifier"),
+            include_str!("fixtures/feedback_amplifier.rs")
+        );
+        assert_eq!(
+            compile(&parse_program(QUINE), "Quine"),
+            include_str!("fixtures/quine.rs")
+        );
+        assert_eq!(
+            compile(&parse_program(SELF_MODIFYING), "SelfModifying"),
+            include_str!("fixtures/self_modifying.rs")
+        );
+        assert_eq!(
+            compile(&parse_program(SPINNER), "Spinner"),
+            include_str!("fixtures/spinner.rs")
+        );
+    }
+
+    #[test]
+    fn test_compiled_feedback_loop() {
+        let mut amplifiers: Vec<FeedbackAmplifier> = [9, 8, 7, 6, 5]
+            .iter()
+            .map(|&phase| {
+                let mut amplifier = FeedbackAmplifier::new();
+                amplifier.push_input(phase);
+                amplifier
+            })
+            .collect();
+        let mut signal = 0;
+        'feedback: loop {
+            for amplifier in amplifiers.iter_mut() {
+                amplifier.push_input(signal);
+                match amplifier.run(RunMode::UntilOutput).unwrap() {
+                    ProgramResult::Output(output) => signal = output,
+                    _ => break 'feedback,
+                }
+            }
+        }
+        assert_eq!(signal, 139629729);
+        assert!(amplifiers.iter().all(|amplifier| amplifier.is_native()));
+
+        let mut machine = Machine::new(parse_program(FEEDBACK_AMPLIFIER));
+        let mut amplifier = FeedbackAmplifier::new();
+        for &value in &[5, 0, 1] {
+            machine.push_input(value);
+            amplifier.push_input(value);
+        }
+        let expected = machine.run(RunMode::ToHalt);
+        assert_eq!(amplifier.run(RunMode::ToHalt), expected);
+        assert_eq!(
+            expected,
+            Err(IntcodeError::InputExhausted {
+                pc: 6,
+                instruction: 3
+            })
+        );
+        assert_eq!(amplifier.output(), machine.output());
+        assert_eq!(amplifier.machine().steps(), machine.steps());
+        assert_eq!(
+            amplifier.run(RunMode::UntilInput),
+            Ok(ProgramResult::NeedsInput)
+        );
+    }
+
+    #[test]
+    fn test_compiled_relative_mode() {
+        let mut quine = Quine::new();
+        assert_eq!(quine.run(RunMode::ToHalt), Ok(ProgramResult::Finished));
+        assert_eq!(quine.output(), &parse_program(QUINE));
+        assert!(quine.is_native());
+        assert_eq!(quine.machine().relative_base(), 16);
+    }
+
+    #[test]
+    fn test_self_modification_falls_back() {
+        let mut amplifier = SelfModifying::new();
+        assert_eq!(amplifier.run(RunMode::ToHalt), Ok(ProgramResult::Finished));
+        assert_eq!(amplifier.output(), &vec![7, 12]);
+        assert!(!amplifier.is_native());
+        assert_eq!(amplifier.machine().read(0), 1102);
+    }
+
+    #[test]
+    fn test_limits_stop_native_code() {
+        let limits = Limits {
+            steps: Some(1000),
+            time: None,
+        };
+        let mut spinner = Spinner::new();
+        spinner.set_limits(limits);
+        assert_eq!(
+            spinner.run(RunMode::ToHalt),
+            Err(IntcodeError::LimitExceeded {
+                pc: 0,
+                instruction: 1105,
+                limit: Limit::Steps(1000),
+                hint: None,
+            })
+        );
+        assert!(spinner.is_native());
+
+        let time = Duration::from_millis(10);
+        let mut spinner = Spinner::new();
+        spinner.set_limits(Limits {
+            steps: None,
+            time: Some(time),
+        });
+        match spinner.run(RunMode::ToHalt) {
+            Err(IntcodeError::LimitExceeded { limit, .. }) => assert_eq!(limit, Limit::Time(time)),
+            other => panic!("unexpected {:?}", other),
+        }
+
+        // stops at the same instruction as the interpreter
+        let mut machine = Machine::new(parse_program(FEEDBACK_AMPLIFIER));
+        let mut amplifier = FeedbackAmplifier::new();
+        for &value in &[5, 0, 1, 2, 3] {
+            machine.push_input(value);
+            amplifier.push_input(value);
+        }
+        let limits = Limits {
+            steps: Some(20),
+            time: None,
+        };
+        machine.set_limits(limits);
+        amplifier.set_limits(limits);
+        let error = amplifier.run(RunMode::ToHalt).unwrap_err();
+        match (machine.run(RunMode::ToHalt).unwrap_err(), error) {
+            (
+                IntcodeError::LimitExceeded {
+                    pc, instruction, ..
+                },
+                IntcodeError::LimitExceeded {
+                    pc: native_pc,
+                    instruction: native_instruction,
+                    ..
+                },
+            ) => assert_eq!((pc, instruction), (native_pc, native_instruction)),
+            other => panic!("unexpected {:?}", other),
+        }
+        assert_eq!(amplifier.output(), machine.output());
+        assert_eq!(amplifier.machine().steps(), 20);
+    }
+
+    #[test]
+    fn test_ranges() {
+        let addresses: BTreeSet<usize> = [0, 1, 2, 5, 7, 8].iter().copied().collect();
+        assert_eq!(ranges(&addresses), vec!["0..=2", "5", "7..=8"]);
+    }
+}
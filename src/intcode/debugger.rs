@@ -0,0 +1,436 @@
+use std::collections::{BTreeSet, VecDeque};
+use std::fs::File;
+use std::io::{stdin, stdout, BufRead, BufReader, BufWriter, Result as IoResult, Write};
+
+use super::disasm::disassemble_at;
+use super::snapshot::Snapshot;
+use super::watch::{WatchKind, Watchpoint};
+use super::{Machine, OperationResult};
+
+/// Number of instructions that `back` and `rcontinue` can undo.
+const HISTORY_LIMIT: usize = 1_000_000;
+
+const HELP: &str = "\
+step [n]           execute n instructions (default 1)
+continue           run until a breakpoint, input is needed or the program halts
+back [n]           undo the last n instructions (default 1)
+rcontinue          run backwards to the previous breakpoint or the oldest
+                   remembered instruction
+break [addr]       set a breakpoint, or list breakpoints without an address
+delete <addr>      remove a breakpoint
+watch [start] [end]
+                   stop when memory from start up to end is written, or list
+                   watchpoints without an address
+rwatch <start> [end]
+                   stop when memory is read
+awatch <start> [end]
+                   stop when memory is read or written
+unwatch <start> [end]
+                   remove watchpoints on exactly that range
+regs               print pc and relative base
+list [addr] [n]    disassemble n instructions starting at addr (default pc)
+mem <start> [end]  print memory from start up to, not including, end
+poke <addr> <val>  write val to memory
+input <vals...>    queue input values
+snapshot           remember the current machine state
+restore            go back to the remembered state
+save <file>        write the machine state to a file
+load <file>        continue from a state written by save
+quit               leave the debugger";
+
+/// Interactive front end to a `Machine` that is driven one command at a time.
+pub struct Debugger {
+    machine: Machine,
+    breakpoints: BTreeSet<usize>,
+    printed: usize,
+    saved: Option<Snapshot<VecDeque<i64>>>,
+}
+
+impl Debugger {
+    pub fn new(program: Vec<i64>) -> Debugger {
+        let mut machine = Machine::new(program);
+        machine.record_history(HISTORY_LIMIT);
+        Debugger {
+            machine,
+            breakpoints: BTreeSet::new(),
+            printed: 0,
+            saved: None,
+        }
+    }
+
+    /// Reads commands from stdin until the user quits or closes the stream.
+    pub fn repl(&mut self) -> IoResult<()> {
+        let stdin = stdin();
+        let mut out = stdout();
+        self.print_current(&mut out)?;
+        loop {
+            write!(out, "(icdb) ")?;
+            out.flush()?;
+            let mut line = String::new();
+            if stdin.lock().read_line(&mut line)? == 0 || !self.execute(&line, &mut out)? {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Executes one command line. Returns false once the user asks to quit.
+    pub fn execute(&mut self, line: &str, out: &mut impl Write) -> IoResult<bool> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let (command, args) = match words.split_first() {
+            Some((command, args)) => (*command, args),
+            None => return Ok(true),
+        };
+        match (command, args) {
+            ("save", [path]) => {
+                let file = BufWriter::new(File::create(path)?);
+                self.machine.snapshot().write_to(file)?;
+                return Ok(true);
+            }
+            ("load", [path]) => {
+                let snapshot = Snapshot::read_from(BufReader::new(File::open(path)?))?;
+                self.machine = Machine::from_snapshot(snapshot, vec![]);
+                self.machine.record_history(HISTORY_LIMIT);
+                self.printed = 0;
+                self.print_current(out)?;
+                return Ok(true);
+            }
+            ("save", _) | ("load", _) => {
+                writeln!(out, "usage: {} <file>", command)?;
+                return Ok(true);
+            }
+            _ => (),
+        }
+        let numbers: Vec<i64> = match args.iter().map(|arg| arg.parse()).collect() {
+            Ok(numbers) => numbers,
+            Err(_) => {
+                writeln!(out, "arguments must be numbers")?;
+                return Ok(true);
+            }
+        };
+        // input values and the value to poke may be negative, addresses and counts not
+        let unsigned = match command {
+            "i" | "input" => 0,
+            "p" | "poke" => 1,
+            _ => numbers.len(),
+        };
+        if numbers.iter().take(unsigned).any(|&n| n < 0) {
+            writeln!(
+                out,
+                "usage: addresses and counts of {} can't be negative",
+                command
+            )?;
+            return Ok(true);
+        }
+        let address = |index: usize| numbers.get(index).map(|&n| n as usize);
+
+        match command {
+            "s" | "step" => {
+                for _ in 0..address(0).unwrap_or(1) {
+                    if !self.step(out)? {
+                        break;
+                    }
+                }
+                self.print_current(out)?;
+            }
+            "c" | "continue" => {
+                while self.step(out)? {
+                    let pc = self.machine.program_counter();
+                    if self.breakpoints.contains(&pc) {
+                        writeln!(out, "breakpoint at {:04}", pc)?;
+                        break;
+                    }
+                }
+                self.print_current(out)?;
+            }
+            "rs" | "back" => {
+                for _ in 0..address(0).unwrap_or(1) {
+                    if !self.step_back(out)? {
+                        break;
+                    }
+                }
+                self.print_current(out)?;
+            }
+            "rc" | "rcontinue" => {
+                while self.step_back(out)? {
+                    let pc = self.machine.program_counter();
+                    if self.breakpoints.contains(&pc) {
+                        writeln!(out, "breakpoint at {:04}", pc)?;
+                        break;
+                    }
+                }
+                self.print_current(out)?;
+            }
+            "b" | "break" => match address(0) {
+                Some(address) => {
+                    self.breakpoints.insert(address);
+                }
+                None => {
+                    for breakpoint in &self.breakpoints {
+                        writeln!(
+                            out,
+                            "{}",
+                            disassemble_at(self.machine.memory(), *breakpoint)
+                        )?;
+                    }
+                }
+            },
+            "d" | "delete" => match address(0) {
+                Some(address) if self.breakpoints.remove(&address) => (),
+                _ => writeln!(out, "no such breakpoint")?,
+            },
+            "watch" if numbers.is_empty() => {
+                for watchpoint in self.machine.watchpoints() {
+                    writeln!(out, "{}", watchpoint)?;
+                }
+            }
+            "watch" | "rwatch" | "awatch" | "unwatch" => match address(0) {
+                Some(start) => {
+                    let end = address(1).unwrap_or(start + 1).max(start + 1);
+                    let kind = match command {
+                        "rwatch" => WatchKind::Read,
+                        "awatch" => WatchKind::ReadWrite,
+                        _ => WatchKind::Write,
+                    };
+                    if command != "unwatch" {
+                        self.machine
+                            .add_watchpoint(Watchpoint::new(start, end, kind));
+                    } else {
+                        let mut removed = false;
+                        for &kind in &[WatchKind::Read, WatchKind::Write, WatchKind::ReadWrite] {
+                            removed |= self
+                                .machine
+                                .remove_watchpoint(Watchpoint::new(start, end, kind));
+                        }
+                        if !removed {
+                            writeln!(out, "no such watchpoint")?;
+                        }
+                    }
+                }
+                None => writeln!(out, "usage: {} <start> [end]", command)?,
+            },
+            "r" | "regs" => writeln!(
+                out,
+                "pc {:04}  rb {}{}",
+                self.machine.program_counter(),
+                self.machine.relative_base(),
+                if self.machine.is_halted() {
+                    "  (halted)"
+                } else {
+                    ""
+                }
+            )?,
+            "l" | "list" => {
+                let mut next = address(0).unwrap_or_else(|| self.machine.program_counter());
+                for _ in 0..address(1).unwrap_or(10) {
+                    let line = disassemble_at(self.machine.memory(), next);
+                    writeln!(out, "{}", line)?;
+                    next += line.size();
+                }
+            }
+            "x" | "mem" => match address(0) {
+                Some(start) => {
+                    let end = address(1).unwrap_or(start + 1).max(start);
+                    for row in (start..end).step_by(8) {
+                        let values: Vec<String> = (row..end.min(row + 8))
+                            .map(|address| self.machine.memory()[address].to_string())
+                            .collect();
+                        writeln!(out, "{:04}: {}", row, values.join(" "))?;
+                    }
+                }
+                None => writeln!(out, "usage: mem <start> [end]")?,
+            },
+            "p" | "poke" => match (address(0), numbers.get(1)) {
+                (Some(address), Some(&value)) => self.machine.memory_mut()[address] = value,
+                _ => writeln!(out, "usage: poke <addr> <value>")?,
+            },
+            "i" | "input" => {
+                for &value in &numbers {
+                    self.machine.push_input(value);
+                }
+            }
+            "snapshot" => self.saved = Some(self.machine.snapshot()),
+            "restore" => match &self.saved {
+                Some(snapshot) => {
+                    self.machine.restore(snapshot);
+                    self.print_current(out)?;
+                }
+                None => writeln!(out, "no snapshot taken")?,
+            },
+            "h" | "help" => writeln!(out, "{}", HELP)?,
+            "q" | "quit" => return Ok(false),
+            _ => writeln!(out, "unknown command {}, try help", command)?,
+        }
+        Ok(true)
+    }
+
+    /// Executes one instruction, reporting anything noteworthy. Returns
+    /// whether execution can carry on.
+    fn step(&mut self, out: &mut impl Write) -> IoResult<bool> {
+        let result = self.machine.perform_operation();
+        for value in &self.machine.output()[self.printed..] {
+            writeln!(out, "output: {}", value)?;
+        }
+        self.printed = self.machine.output().len();
+        match result {
+            Ok(OperationResult::Continue) | Ok(OperationResult::Output(_)) => Ok(true),
+            Ok(OperationResult::Watchpoint(hit)) => {
+                writeln!(out, "watchpoint: {}", hit)?;
+                Ok(false)
+            }
+            Ok(OperationResult::NeedsInput) => {
+                writeln!(out, "waiting for input")?;
+                Ok(false)
+            }
+            Ok(OperationResult::Finished) => {
+                writeln!(out, "halted")?;
+                Ok(false)
+            }
+            Err(e) => {
+                writeln!(out, "error: {}", e)?;
+                Ok(false)
+            }
+        }
+    }
+
+    /// Undoes one instruction, taking back anything it printed. Returns
+    /// whether there is more history to go back through.
+    fn step_back(&mut self, out: &mut impl Write) -> IoResult<bool> {
+        match self.machine.step_back() {
+            Some(undo) => {
+                if undo.output.is_some() {
+                    self.machine.output_mut().pop();
+                    self.printed = self.machine.output().len();
+                }
+                Ok(true)
+            }
+            None => {
+                writeln!(out, "at start of history")?;
+                Ok(false)
+            }
+        }
+    }
+
+    fn print_current(&self, out: &mut impl Write) -> IoResult<()> {
+        let pc = self.machine.program_counter();
+        writeln!(out, "=> {}", disassemble_at(self.machine.memory(), pc))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::parse_program;
+
+    fn session(debugger: &mut Debugger, commands: &[&str]) -> String {
+        let mut out = vec![];
+        for command in commands {
+            debugger.execute(command, &mut out).unwrap();
+        }
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_step_and_breakpoints() {
+        let mut debugger = Debugger::new(parse_program("1101,1,2,9,1101,3,4,10,99,0,0"));
+        assert_eq!(
+            session(&mut debugger, &["step", "break 8", "continue", "regs"]),
+            "=> 0004: 1101,3,4,10              ADD #3, #4, [10]\n\
+             breakpoint at 0008\n\
+             => 0008: 99                       HLT\n\
+             pc 0008  rb 0\n"
+        );
+        assert_eq!(
+            session(&mut debugger, &["mem 9 11", "delete 8", "delete 8"]),
+            "0009: 3 7\nno such breakpoint\n"
+        );
+    }
+
+    #[test]
+    fn test_watchpoints() {
+        let mut debugger = Debugger::new(parse_program("1101,1,2,9,1001,9,3,9,99,0"));
+        assert_eq!(
+            session(&mut debugger, &["watch 9", "watch", "c", "unwatch 9", "c"]),
+            "0009..0010 w\n\
+             watchpoint: [9] written by 1101 at pc 0: 0 -> 3\n\
+             => 0004: 1001,9,3,9               ADD [9], #3, [9]\n\
+             halted\n\
+             => 0008: 99                       HLT\n"
+        );
+    }
+
+    #[test]
+    fn test_snapshot_commands() {
+        let mut debugger = Debugger::new(parse_program("1001,5,1,5,99,0"));
+        assert_eq!(
+            session(&mut debugger, &["restore", "snapshot", "c", "mem 5"]),
+            "no snapshot taken\nhalted\n=> 0004: 99                       HLT\n0005: 1\n"
+        );
+        assert_eq!(
+            session(&mut debugger, &["poke 5 7", "restore", "mem 5"]),
+            "=> 0000: 1001,5,1,5               ADD [5], #1, [5]\n0005: 0\n"
+        );
+
+        let path = std::env::temp_dir().join("icdb-test-snapshot");
+        let path = path.to_str().unwrap();
+        session(&mut debugger, &[&format!("save {}", path), "step"]);
+        assert_eq!(
+            session(&mut debugger, &[&format!("load {}", path), "regs"]),
+            "=> 0000: 1001,5,1,5               ADD [5], #1, [5]\npc 0000  rb 0\n"
+        );
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_reverse_execution() {
+        let mut debugger = Debugger::new(parse_program("3,11,1001,11,1,11,4,11,1105,1,0,0"));
+        assert_eq!(
+            session(
+                &mut debugger,
+                &["input 1 5", "break 6", "c", "c", "back", "mem 11"]
+            ),
+            "breakpoint at 0006\n\
+             => 0006: 4,11                     OUT [11]\n\
+             output: 2\n\
+             breakpoint at 0006\n\
+             => 0006: 4,11                     OUT [11]\n\
+             => 0002: 1001,11,1,11             ADD [11], #1, [11]\n\
+             0011: 5\n"
+        );
+        assert_eq!(
+            session(&mut debugger, &["rc", "rc", "mem 11", "c", "c"]),
+            "breakpoint at 0006\n\
+             => 0006: 4,11                     OUT [11]\n\
+             at start of history\n\
+             => 0000: 3,11                     IN [11]\n\
+             0011: 0\n\
+             breakpoint at 0006\n\
+             => 0006: 4,11                     OUT [11]\n\
+             output: 2\n\
+             breakpoint at 0006\n\
+             => 0006: 4,11                     OUT [11]\n"
+        );
+    }
+
+    #[test]
+    fn test_input_and_poke() {
+        let mut debugger = Debugger::new(parse_program("3,7,4,7,4,8,99,0,0"));
+        assert_eq!(
+            session(&mut debugger, &["continue", "input 42", "poke 8 5", "c"]),
+            "waiting for input\n\
+             => 0000: 3,7                      IN [7]\n\
+             output: 42\n\
+             output: 5\n\
+             halted\n\
+             => 0006: 99                       HLT\n"
+        );
+        assert_eq!(
+            session(
+                &mut debugger,
+                &["poke -1 5", "break -3", "poke 0 -5", "mem 0", "break"]
+            ),
+            "usage: addresses and counts of poke can't be negative\n\
+             usage: addresses and counts of break can't be negative\n\
+             0000: -5\n"
+        );
+    }
+}
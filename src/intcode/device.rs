@@ -0,0 +1,213 @@
+use std::collections::VecDeque;
+use std::fmt::Display;
+use std::io::{stdin, stdout, BufRead, StdinLock, Stdout, Write};
+use std::sync::mpsc::{Receiver, Sender};
+
+use super::parse_program;
+
+/// Source of values for the Intcode input instruction. `T` is the machine's
+/// word type.
+pub trait InputDevice<T = i64> {
+    /// Returns the next input value, or `None` if there is none available.
+    fn read(&mut self) -> Option<T>;
+}
+
+/// Sink for values printed by the Intcode output instruction.
+pub trait OutputDevice<T = i64> {
+    fn write(&mut self, value: T);
+}
+
+impl<T> InputDevice<T> for VecDeque<T> {
+    fn read(&mut self) -> Option<T> {
+        self.pop_front()
+    }
+}
+
+impl<T> OutputDevice<T> for VecDeque<T> {
+    fn write(&mut self, value: T) {
+        self.push_back(value);
+    }
+}
+
+/// Records every printed value.
+impl<T> OutputDevice<T> for Vec<T> {
+    fn write(&mut self, value: T) {
+        self.push(value);
+    }
+}
+
+/// Blocks until a value arrives, and reports no input once all senders are gone.
+impl<T> InputDevice<T> for Receiver<T> {
+    fn read(&mut self) -> Option<T> {
+        self.recv().ok()
+    }
+}
+
+impl<T> OutputDevice<T> for Sender<T> {
+    fn write(&mut self, value: T) {
+        // a hung up receiver is not interested in any more values
+        let _ = self.send(value);
+    }
+}
+
+pub struct FnInput<F>(pub F);
+
+impl<T, F: FnMut() -> Option<T>> InputDevice<T> for FnInput<F> {
+    fn read(&mut self) -> Option<T> {
+        (self.0)()
+    }
+}
+
+/// Reads comma-separated integers a line at a time, skipping values that
+/// don't parse.
+pub struct LineReader<R> {
+    reader: R,
+    pending: VecDeque<i64>,
+}
+
+impl<R: BufRead> LineReader<R> {
+    pub fn new(reader: R) -> LineReader<R> {
+        LineReader {
+            reader,
+            pending: VecDeque::new(),
+        }
+    }
+}
+
+impl<R: BufRead> InputDevice for LineReader<R> {
+    fn read(&mut self) -> Option<i64> {
+        let mut line = String::new();
+        while self.pending.is_empty() {
+            line.clear();
+            if self.reader.read_line(&mut line).ok()? == 0 {
+                return None;
+            }
+            self.pending.extend(parse_program(&line));
+        }
+        self.pending.pop_front()
+    }
+}
+
+/// Writes one value per line.
+pub struct Printer<W> {
+    writer: W,
+}
+
+impl<W: Write> Printer<W> {
+    pub fn new(writer: W) -> Printer<W> {
+        Printer { writer }
+    }
+}
+
+impl Printer<Stdout> {
+    pub fn stdout() -> Printer<Stdout> {
+        Printer::new(stdout())
+    }
+}
+
+impl<T: Display, W: Write> OutputDevice<T> for Printer<W> {
+    fn write(&mut self, value: T) {
+        writeln!(self.writer, "{}", value).expect("failed to write output");
+    }
+}
+
+/// Text terminal for ASCII capable programs. Input lines are fed to the
+/// program character by character, output values in the ASCII range are
+/// printed as characters and anything else as a plain number.
+pub struct AsciiTerminal<R, W> {
+    reader: R,
+    writer: W,
+    pending: VecDeque<i64>,
+}
+
+impl<R: BufRead, W: Write> AsciiTerminal<R, W> {
+    pub fn new(reader: R, writer: W) -> AsciiTerminal<R, W> {
+        AsciiTerminal {
+            reader,
+            writer,
+            pending: VecDeque::new(),
+        }
+    }
+}
+
+impl AsciiTerminal<StdinLock<'static>, Stdout> {
+    pub fn stdio() -> AsciiTerminal<StdinLock<'static>, Stdout> {
+        AsciiTerminal::new(stdin().lock(), stdout())
+    }
+}
+
+impl<R: BufRead, W: Write> InputDevice for AsciiTerminal<R, W> {
+    fn read(&mut self) -> Option<i64> {
+        if self.pending.is_empty() {
+            self.writer.flush().ok()?;
+            let mut line = String::new();
+            if self.reader.read_line(&mut line).ok()? == 0 {
+                return None;
+            }
+            if !line.ends_with('\n') {
+                line.push('\n');
+            }
+            self.pending.extend(line.bytes().map(i64::from));
+        }
+        self.pending.pop_front()
+    }
+}
+
+impl<R: BufRead, W: Write> OutputDevice for AsciiTerminal<R, W> {
+    fn write(&mut self, value: i64) {
+        match value {
+            0..=127 => write!(self.writer, "{}", value as u8 as char),
+            _ => writeln!(self.writer, "{}", value),
+        }
+        .expect("failed to write output");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::channel;
+
+    #[test]
+    fn test_channel_devices() {
+        let (mut sender, mut receiver) = channel();
+        sender.write(3);
+        sender.write(4);
+        drop(sender);
+        assert_eq!(receiver.read(), Some(3));
+        assert_eq!(receiver.read(), Some(4));
+        assert_eq!(receiver.read(), None);
+    }
+
+    #[test]
+    fn test_closure_input() {
+        let mut counter = 0;
+        let mut input = FnInput(|| {
+            counter += 1;
+            Some(counter)
+        });
+        assert_eq!(input.read(), Some(1));
+        assert_eq!(input.read(), Some(2));
+    }
+
+    #[test]
+    fn test_line_reader() {
+        let mut input = LineReader::new("1\n\nfoo\n-2, 3\n".as_bytes());
+        assert_eq!(input.read(), Some(1));
+        assert_eq!(input.read(), Some(-2));
+        assert_eq!(input.read(), Some(3));
+        assert_eq!(input.read(), None);
+    }
+
+    #[test]
+    fn test_ascii_terminal() {
+        let mut terminal = AsciiTerminal::new("go\n".as_bytes(), vec![]);
+        let read: Vec<i64> = std::iter::from_fn(|| terminal.read()).collect();
+        assert_eq!(read, vec![103, 111, 10]);
+
+        for &value in &[104, 105, 10, 19349] {
+            terminal.write(value);
+        }
+        assert_eq!(terminal.writer, b"hi\n19349\n");
+    }
+}
@@ -0,0 +1,167 @@
+use std::fmt;
+
+use super::{parse_opcode, AddressingMode, Memory, Operation};
+
+/// One line of a disassembly listing: either a decoded instruction or a
+/// single word that doesn't decode as one.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Line {
+    Instruction {
+        address: usize,
+        operation: Operation,
+        words: Vec<i64>,
+    },
+    Data {
+        address: usize,
+        value: i64,
+    },
+}
+
+impl Line {
+    /// Number of memory words covered by the line.
+    pub fn size(&self) -> usize {
+        match self {
+            Line::Instruction { words, .. } => words.len(),
+            Line::Data { .. } => 1,
+        }
+    }
+}
+
+/// Decodes `program` front to back. Words that aren't a valid opcode, whose
+/// instruction would run past the end of the program, or that carry mode
+/// digits the instruction has no parameters for, become data. That way the
+/// listing assembles back into the same program.
+pub fn disassemble(program: &[i64]) -> Vec<Line> {
+    let mut lines = vec![];
+    let mut address = 0;
+    while address < program.len() {
+        let value = program[address];
+        match parse_opcode(value) {
+            Some(operation)
+                if address + operation.len() <= program.len()
+                    && value < 10i64.pow(operation.len() as u32 + 1) =>
+            {
+                let words = program[address..address + operation.len()].to_vec();
+                lines.push(Line::Instruction {
+                    address,
+                    operation,
+                    words,
+                });
+                address += operation.len();
+            }
+            _ => {
+                lines.push(Line::Data { address, value });
+                address += 1;
+            }
+        }
+    }
+    lines
+}
+
+/// Decodes the single instruction at `address` of a running machine's memory.
+pub fn disassemble_at(memory: &Memory, address: usize) -> Line {
+    let value = memory.read(address);
+    match parse_opcode(value) {
+        Some(operation) => Line::Instruction {
+            address,
+            operation,
+            words: (address..address + operation.len())
+                .map(|address| memory.read(address))
+                .collect(),
+        },
+        None => Line::Data { address, value },
+    }
+}
+
+pub fn format_operand(mode: AddressingMode, parameter: i64) -> String {
+    match mode {
+        AddressingMode::Indirect => format!("[{}]", parameter),
+        AddressingMode::Immediate => format!("#{}", parameter),
+        AddressingMode::Relative if parameter < 0 => format!("[rb{}]", parameter),
+        AddressingMode::Relative => format!("[rb+{}]", parameter),
+    }
+}
+
+impl fmt::Display for Line {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Line::Instruction {
+                address,
+                operation,
+                words,
+            } => {
+                let raw = words
+                    .iter()
+                    .map(|word| word.to_string())
+                    .collect::<Vec<String>>()
+                    .join(",");
+                let operands = operation
+                    .parameter_modes()
+                    .into_iter()
+                    .zip(&words[1..])
+                    .map(|(mode, &parameter)| format_operand(mode, parameter))
+                    .collect::<Vec<String>>()
+                    .join(", ");
+                write!(f, "{:04}: {:<24} {}", address, raw, operation.mnemonic())?;
+                if !operands.is_empty() {
+                    write!(f, " {}", operands)?;
+                }
+                Ok(())
+            }
+            Line::Data { address, value } => {
+                write!(f, "{:04}: {:<24} DB {}", address, value, value)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::parse_program;
+
+    fn listing(program: &str) -> Vec<String> {
+        disassemble(&parse_program(program))
+            .iter()
+            .map(|line| line.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_disassemble_day5_example() {
+        assert_eq!(
+            listing("1002,4,3,4,33"),
+            vec![
+                "0000: 1002,4,3,4               MUL [4], #3, [4]",
+                "0004: 33                       DB 33",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_disassemble_relative_and_truncated() {
+        assert_eq!(
+            listing("109,-1,204,5,99,0,1101,1"),
+            vec![
+                "0000: 109,-1                   ARB #-1",
+                "0002: 204,5                    OUT [rb+5]",
+                "0004: 99                       HLT",
+                "0005: 0                        DB 0",
+                "0006: 1101                     DB 1101",
+                "0007: 1                        DB 1",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_disassemble_surplus_modes() {
+        assert_eq!(
+            listing("10004,5,100099"),
+            vec![
+                "0000: 10004                    DB 10004",
+                "0001: 5                        DB 5",
+                "0002: 100099                   DB 100099",
+            ]
+        );
+    }
+}
@@ -0,0 +1,123 @@
+use std::error::Error;
+use std::fmt;
+
+use super::limit::{Limit, LoopHint};
+
+/// Everything that can go wrong while executing an Intcode program. Each
+/// variant records the program counter and the instruction word at fault.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum IntcodeError {
+    InvalidOpcode {
+        pc: usize,
+        value: i64,
+    },
+    InvalidMode {
+        pc: usize,
+        instruction: i64,
+        parameter: usize,
+    },
+    NegativeAddress {
+        pc: usize,
+        instruction: i64,
+        address: i64,
+    },
+    InputExhausted {
+        pc: usize,
+        instruction: i64,
+    },
+    WriteToImmediate {
+        pc: usize,
+        instruction: i64,
+    },
+    /// A result, address or relative base doesn't fit the machine's types.
+    Overflow {
+        pc: usize,
+        instruction: i64,
+    },
+    /// The machine ran for too long, see `Machine::set_limits`.
+    LimitExceeded {
+        pc: usize,
+        instruction: i64,
+        limit: Limit,
+        hint: Option<LoopHint>,
+    },
+}
+
+impl IntcodeError {
+    pub fn pc(&self) -> usize {
+        match *self {
+            IntcodeError::InvalidOpcode { pc, .. }
+            | IntcodeError::InvalidMode { pc, .. }
+            | IntcodeError::NegativeAddress { pc, .. }
+            | IntcodeError::InputExhausted { pc, .. }
+            | IntcodeError::WriteToImmediate { pc, .. }
+            | IntcodeError::Overflow { pc, .. }
+            | IntcodeError::LimitExceeded { pc, .. } => pc,
+        }
+    }
+
+    pub fn instruction(&self) -> i64 {
+        match *self {
+            IntcodeError::InvalidOpcode { value, .. } => value,
+            IntcodeError::InvalidMode { instruction, .. }
+            | IntcodeError::NegativeAddress { instruction, .. }
+            | IntcodeError::InputExhausted { instruction, .. }
+            | IntcodeError::WriteToImmediate { instruction, .. }
+            | IntcodeError::Overflow { instruction, .. }
+            | IntcodeError::LimitExceeded { instruction, .. } => instruction,
+        }
+    }
+}
+
+impl fmt::Display for IntcodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            IntcodeError::InvalidOpcode { pc, value } => {
+                write!(f, "invalid opcode {} at pc {}", value, pc)
+            }
+            IntcodeError::InvalidMode {
+                pc,
+                instruction,
+                parameter,
+            } => write!(
+                f,
+                "invalid addressing mode for parameter {} of {} at pc {}",
+                parameter, instruction, pc
+            ),
+            IntcodeError::NegativeAddress {
+                pc,
+                instruction,
+                address,
+            } => write!(
+                f,
+                "negative address {} accessed by {} at pc {}",
+                address, instruction, pc
+            ),
+            IntcodeError::InputExhausted { pc, instruction } => {
+                write!(f, "no input left for {} at pc {}", instruction, pc)
+            }
+            IntcodeError::WriteToImmediate { pc, instruction } => write!(
+                f,
+                "write to immediate parameter by {} at pc {}",
+                instruction, pc
+            ),
+            IntcodeError::Overflow { pc, instruction } => {
+                write!(f, "arithmetic overflow in {} at pc {}", instruction, pc)
+            }
+            IntcodeError::LimitExceeded {
+                pc,
+                instruction,
+                limit,
+                hint,
+            } => {
+                write!(f, "{} reached by {} at pc {}", limit, instruction, pc)?;
+                match hint {
+                    Some(hint) => write!(f, "; {}", hint),
+                    None => Ok(()),
+                }
+            }
+        }
+    }
+}
+
+impl Error for IntcodeError {}
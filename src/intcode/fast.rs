@@ -0,0 +1,464 @@
+use std::collections::VecDeque;
+use std::time::Instant;
+
+use super::limit::Limits;
+use super::memory::{to_address, NegativeAddress};
+use super::profile::{self, Profiler};
+use super::{
+    decode_error, mode_str_to_addressing_mode, parse_opcode, AddressingMode, InputDevice,
+    IntcodeError, Memory, Operation, OperationResult, OutputDevice, ProgramResult, RunMode,
+};
+
+/// Addresses below this live in a flat vector, anything above in paged memory.
+const FLAT_LIMIT: usize = 1 << 20;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+enum Opcode {
+    Add,
+    Multiply,
+    Input,
+    Output,
+    JumpIfTrue,
+    JumpIfFalse,
+    LessThan,
+    Equals,
+    AdjustRelativeBase,
+    Halt,
+}
+
+/// An instruction together with its parameter words, decoded once.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+struct Decoded {
+    opcode: Opcode,
+    modes: [AddressingMode; 3],
+    parameters: [i64; 3],
+    len: usize,
+    instruction: i64,
+}
+
+/// Interpreter that decodes every instruction once and caches the result per
+/// address. Writes drop cached instructions they overlap, so self-modifying
+/// programs behave exactly as on `Machine`. There are no tracers, watchpoints
+/// or undo log, only the profiler; use `Machine` when those are needed.
+#[derive(Clone)]
+pub struct CachedMachine<I = VecDeque<i64>, O = Vec<i64>> {
+    flat: Vec<i64>,
+    far: Memory,
+    cache: Vec<Option<Decoded>>,
+    program_counter: usize,
+    relative_base: i64,
+    input: I,
+    output: O,
+    halted: bool,
+    steps: u64,
+    limits: Limits,
+    deadline: Option<Instant>,
+    profiler: Option<Profiler>,
+}
+
+impl CachedMachine {
+    #[cfg(test)]
+    pub fn new(program: Vec<i64>) -> CachedMachine {
+        CachedMachine::with_devices(program, VecDeque::new(), vec![])
+    }
+}
+
+impl<O> CachedMachine<VecDeque<i64>, O> {
+    pub fn push_input(&mut self, value: i64) {
+        self.input.push_back(value);
+    }
+}
+
+impl<I: InputDevice, O: OutputDevice> CachedMachine<I, O> {
+    pub fn with_devices(mut program: Vec<i64>, input: I, output: O) -> CachedMachine<I, O> {
+        let mut far = Memory::new();
+        if program.len() > FLAT_LIMIT {
+            for (offset, value) in program.split_off(FLAT_LIMIT).into_iter().enumerate() {
+                far.write(FLAT_LIMIT + offset, value);
+            }
+        }
+        CachedMachine {
+            cache: vec![None; program.len()],
+            flat: program,
+            far,
+            program_counter: 0,
+            relative_base: 0,
+            input,
+            output,
+            halted: false,
+            steps: 0,
+            limits: Limits::default(),
+            deadline: None,
+            profiler: profile::profiler(),
+        }
+    }
+
+    pub fn read(&self, address: usize) -> i64 {
+        match self.flat.get(address) {
+            Some(&value) => value,
+            None if address < FLAT_LIMIT => 0,
+            None => self.far.read(address),
+        }
+    }
+
+    /// Writes `value` to memory, forgetting any cached instruction it overlaps.
+    pub fn write(&mut self, address: usize, value: i64) {
+        if address >= FLAT_LIMIT {
+            self.far.write(address, value);
+        } else {
+            if address >= self.flat.len() {
+                self.flat.resize(address + 1, 0);
+                self.cache.resize(address + 1, None);
+            }
+            self.flat[address] = value;
+        }
+        // instructions just below the flat limit can have parameters above it
+        let end = (address + 1).min(self.cache.len());
+        for cached in &mut self.cache[address.saturating_sub(3).min(end)..end] {
+            *cached = None;
+        }
+    }
+
+    pub fn output(&self) -> &O {
+        &self.output
+    }
+
+    pub fn output_mut(&mut self) -> &mut O {
+        &mut self.output
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.halted
+    }
+
+    /// Number of instructions executed so far.
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    /// Same contract as `Machine::set_limits`, except that the error carries
+    /// no loop hint; rerun on `Machine` for one.
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+        self.deadline = limits.deadline();
+    }
+
+    /// Same contract as `Machine::run`.
+    pub fn run(&mut self, mode: RunMode) -> Result<ProgramResult, IntcodeError> {
+        loop {
+            match self.perform_operation()? {
+                OperationResult::Output(value) if mode == RunMode::UntilOutput => {
+                    return Ok(ProgramResult::Output(value))
+                }
+                OperationResult::NeedsInput if mode == RunMode::UntilInput => {
+                    return Ok(ProgramResult::NeedsInput)
+                }
+                OperationResult::NeedsInput => {
+                    return Err(IntcodeError::InputExhausted {
+                        pc: self.program_counter,
+                        instruction: self.read(self.program_counter),
+                    })
+                }
+                OperationResult::Finished => return Ok(ProgramResult::Finished),
+                OperationResult::Watchpoint(_)
+                | OperationResult::Output(_)
+                | OperationResult::Continue => continue,
+            }
+        }
+    }
+
+    fn decode(&mut self, pc: usize) -> Result<Decoded, IntcodeError> {
+        let instruction = self.read(pc);
+        let operation = parse_opcode(instruction).ok_or_else(|| decode_error(pc, instruction))?;
+        let mut decoded = Decoded {
+            opcode: match operation {
+                Operation::Addition { .. } => Opcode::Add,
+                Operation::Multiplication { .. } => Opcode::Multiply,
+                Operation::Input { .. } => Opcode::Input,
+                Operation::Print { .. } => Opcode::Output,
+                Operation::JumpIfTrue { .. } => Opcode::JumpIfTrue,
+                Operation::JumpIfFalse { .. } => Opcode::JumpIfFalse,
+                Operation::LessThan { .. } => Opcode::LessThan,
+                Operation::Equals { .. } => Opcode::Equals,
+                Operation::AdjustRelativeBase { .. } => Opcode::AdjustRelativeBase,
+                Operation::Exit => Opcode::Halt,
+            },
+            modes: [AddressingMode::Indirect; 3],
+            parameters: [0; 3],
+            len: operation.len(),
+            instruction,
+        };
+        let mut modes = instruction / 100;
+        for index in 0..decoded.len - 1 {
+            decoded.modes[index] =
+                mode_str_to_addressing_mode(modes % 10).expect("parse_opcode checked the modes");
+            decoded.parameters[index] = self.read(pc + 1 + index);
+            modes /= 10;
+        }
+        if let Some(cached) = self.cache.get_mut(pc) {
+            *cached = Some(decoded);
+        }
+        Ok(decoded)
+    }
+
+    fn load(&self, decoded: &Decoded, index: usize) -> Result<i64, IntcodeError> {
+        let parameter = decoded.parameters[index];
+        let address = match decoded.modes[index] {
+            AddressingMode::Immediate => return Ok(parameter),
+            AddressingMode::Indirect => parameter,
+            AddressingMode::Relative => self.relative_address(parameter)?,
+        };
+        let address = to_address(address).map_err(|e| self.negative_address(e))?;
+        Ok(self.read(address))
+    }
+
+    fn store(&mut self, decoded: &Decoded, index: usize, value: i64) -> Result<(), IntcodeError> {
+        let parameter = decoded.parameters[index];
+        let address = match decoded.modes[index] {
+            AddressingMode::Immediate => {
+                return Err(IntcodeError::WriteToImmediate {
+                    pc: self.program_counter,
+                    instruction: self.read(self.program_counter),
+                })
+            }
+            AddressingMode::Indirect => parameter,
+            AddressingMode::Relative => self.relative_address(parameter)?,
+        };
+        let address = to_address(address).map_err(|e| self.negative_address(e))?;
+        self.write(address, value);
+        Ok(())
+    }
+
+    fn relative_address(&self, offset: i64) -> Result<i64, IntcodeError> {
+        self.relative_base
+            .checked_add(offset)
+            .ok_or_else(|| self.overflow())
+    }
+
+    fn negative_address(&self, NegativeAddress(address): NegativeAddress) -> IntcodeError {
+        IntcodeError::NegativeAddress {
+            pc: self.program_counter,
+            instruction: self.read(self.program_counter),
+            address,
+        }
+    }
+
+    fn overflow(&self) -> IntcodeError {
+        IntcodeError::Overflow {
+            pc: self.program_counter,
+            instruction: self.read(self.program_counter),
+        }
+    }
+
+    /// Executes a single instruction, see `Machine::perform_operation`.
+    pub fn perform_operation(&mut self) -> Result<OperationResult, IntcodeError> {
+        if self.halted {
+            return Ok(OperationResult::Finished);
+        }
+
+        let pc = self.program_counter;
+        if let Some(limit) = self.limits.exceeded(self.steps, self.deadline) {
+            return Err(IntcodeError::LimitExceeded {
+                pc,
+                instruction: self.read(pc),
+                limit,
+                hint: None,
+            });
+        }
+        let decoded = match self.cache.get(pc) {
+            Some(&Some(decoded)) => decoded,
+            _ => self.decode(pc)?,
+        };
+        let mut next = pc + decoded.len;
+        let mut result = OperationResult::Continue;
+        match decoded.opcode {
+            Opcode::Add => {
+                let value = self.load(&decoded, 0)?.checked_add(self.load(&decoded, 1)?);
+                let value = value.ok_or_else(|| self.overflow())?;
+                self.store(&decoded, 2, value)?;
+            }
+            Opcode::Multiply => {
+                let value = self.load(&decoded, 0)?.checked_mul(self.load(&decoded, 1)?);
+                let value = value.ok_or_else(|| self.overflow())?;
+                self.store(&decoded, 2, value)?;
+            }
+            Opcode::LessThan => {
+                let value = self.load(&decoded, 0)? < self.load(&decoded, 1)?;
+                self.store(&decoded, 2, value as i64)?;
+            }
+            Opcode::Equals => {
+                let value = self.load(&decoded, 0)? == self.load(&decoded, 1)?;
+                self.store(&decoded, 2, value as i64)?;
+            }
+            Opcode::JumpIfTrue | Opcode::JumpIfFalse => {
+                let condition = self.load(&decoded, 0)? != 0;
+                let target = self.load(&decoded, 1)?;
+                if condition == (decoded.opcode == Opcode::JumpIfTrue) {
+                    next = to_address(target).map_err(|e| self.negative_address(e))?;
+                }
+            }
+            Opcode::Input => {
+                let value = match self.input.read() {
+                    Some(value) => value,
+                    None => return Ok(OperationResult::NeedsInput),
+                };
+                self.store(&decoded, 0, value)?;
+            }
+            Opcode::Output => {
+                let value = self.load(&decoded, 0)?;
+                self.output.write(value);
+                result = OperationResult::Output(value);
+            }
+            Opcode::AdjustRelativeBase => {
+                let offset = self.load(&decoded, 0)?;
+                self.relative_base = self
+                    .relative_base
+                    .checked_add(offset)
+                    .ok_or_else(|| self.overflow())?;
+            }
+            Opcode::Halt => {
+                self.halted = true;
+                next = pc;
+                result = OperationResult::Finished;
+            }
+        }
+        self.program_counter = next;
+        self.steps += 1;
+        if let Some(profiler) = self.profiler.as_mut() {
+            profiler.record(pc, decoded.instruction, next);
+        }
+        Ok(result)
+    }
+}
+
+// Hooks that let code generated by `compile` share the machine's state, so
+// it can hand over to the interpreter at any instruction. The binary only
+// generates such code, it never builds any.
+#[cfg_attr(not(test), allow(dead_code))]
+impl<I: InputDevice, O: OutputDevice> CachedMachine<I, O> {
+    pub fn input_mut(&mut self) -> &mut I {
+        &mut self.input
+    }
+
+    pub fn program_counter(&self) -> usize {
+        self.program_counter
+    }
+
+    pub fn relative_base(&self) -> i64 {
+        self.relative_base
+    }
+
+    /// Whether `perform_operation` would fail with `LimitExceeded` now.
+    pub fn limit_reached(&self) -> bool {
+        self.limits.exceeded(self.steps, self.deadline).is_some()
+    }
+
+    pub fn set_program_counter(&mut self, pc: usize) {
+        self.program_counter = pc;
+    }
+
+    pub fn set_relative_base(&mut self, relative_base: i64) {
+        self.relative_base = relative_base;
+    }
+
+    pub fn count_step(&mut self) {
+        self.steps += 1;
+    }
+
+    /// The address `offset` words from the relative base, unless negative
+    /// or out of range.
+    pub fn relative(&self, offset: i64) -> Option<usize> {
+        let address = self.relative_base.checked_add(offset)?;
+        to_address(address).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::{parse_program, Machine};
+
+    /// Runs `program` on both interpreters and checks they agree.
+    fn compare(program: &str, input: &[i64]) -> Result<ProgramResult, IntcodeError> {
+        let program = parse_program(program);
+        let mut naive = Machine::new(program.clone());
+        let mut cached = CachedMachine::new(program);
+        for &value in input {
+            naive.push_input(value);
+            cached.push_input(value);
+        }
+        let result = cached.run(RunMode::ToHalt);
+        assert_eq!(naive.run(RunMode::ToHalt), result);
+        assert_eq!(naive.output(), cached.output());
+        assert_eq!(naive.steps(), cached.steps());
+        assert_eq!(naive.program_counter(), cached.program_counter());
+        result
+    }
+
+    #[test]
+    fn test_matches_naive_interpreter() {
+        compare("1,9,10,3,2,3,11,0,99,30,40,50", &[]).unwrap();
+        compare("3,9,8,9,10,9,4,9,99,-1,8", &[8]).unwrap();
+        compare(
+            "109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99",
+            &[],
+        )
+        .unwrap();
+        compare("3,0,4,0,3,0,99", &[1]).unwrap_err();
+        compare("1,0,0,0,3001,0,0,0", &[]).unwrap_err();
+        compare("1105,1,-3", &[]).unwrap_err();
+        compare("11101,1,1,0,99", &[]).unwrap_err();
+        compare("1102,4611686018427387904,2,0,99", &[]).unwrap_err();
+    }
+
+    #[test]
+    fn test_self_modifying_code() {
+        // The first pass through the loop turns the ADD at 0 into a MUL.
+        let program = "1101,3,4,100,4,100,1005,101,20,1101,1,0,101,1101,1102,0,0,1105,1,0,99";
+        compare(program, &[]).unwrap();
+        let mut machine = CachedMachine::new(parse_program(program));
+        machine.run(RunMode::ToHalt).unwrap();
+        assert_eq!(machine.output(), &vec![7, 12]);
+
+        let mut machine = CachedMachine::new(parse_program("4,3,99,5"));
+        machine.run(RunMode::ToHalt).unwrap();
+        machine.write(1, 0);
+        machine.program_counter = 0;
+        machine.halted = false;
+        machine.run(RunMode::ToHalt).unwrap();
+        assert_eq!(machine.output(), &vec![5, 4]);
+    }
+
+    #[test]
+    fn test_limits() {
+        let limits = Limits {
+            steps: Some(3),
+            time: None,
+        };
+        let program = parse_program("1105,1,3,1105,1,0");
+        let mut naive = Machine::new(program.clone());
+        let mut cached = CachedMachine::new(program);
+        naive.set_limits(limits);
+        cached.set_limits(limits);
+        match (naive.run(RunMode::ToHalt), cached.run(RunMode::ToHalt)) {
+            (
+                Err(IntcodeError::LimitExceeded { pc, limit, .. }),
+                Err(IntcodeError::LimitExceeded {
+                    pc: cached_pc,
+                    limit: cached_limit,
+                    hint: None,
+                    ..
+                }),
+            ) => assert_eq!((pc, limit), (cached_pc, cached_limit)),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_far_memory() {
+        let mut machine = CachedMachine::new(parse_program("1101,2,3,1000000000,4,1000000000,99"));
+        machine.run(RunMode::ToHalt).unwrap();
+        assert_eq!(machine.output(), &vec![5]);
+        assert_eq!(machine.flat.len(), 7);
+    }
+}
@@ -0,0 +1,296 @@
+// Generated by `intcode compile`, do not edit.
+
+use std::collections::VecDeque;
+
+use crate::intcode::fast::CachedMachine;
+use crate::intcode::limit::Limits;
+use crate::intcode::network::Node;
+use crate::intcode::{
+    InputDevice, IntcodeError, OperationResult, OutputDevice, ProgramResult, RunMode,
+};
+
+const PROGRAM: [i64; 29] = [
+    3, 26, 1001, 26, -4, 26, 3, 27, 1002, 27, 2, 27,
+    1, 27, 26, 27, 4, 27, 1001, 28, -1, 28, 1005, 28,
+    6, 99, 0, 0, 5,
+];
+
+fn is_code(address: usize) -> bool {
+    matches!(address, 0..=25)
+}
+
+pub struct FeedbackAmplifier<I = VecDeque<i64>, O = Vec<i64>> {
+    machine: CachedMachine<I, O>,
+    native: bool,
+}
+
+impl FeedbackAmplifier {
+    pub fn new() -> FeedbackAmplifier {
+        FeedbackAmplifier::with_devices(VecDeque::new(), vec![])
+    }
+}
+
+impl Default for FeedbackAmplifier {
+    fn default() -> FeedbackAmplifier {
+        FeedbackAmplifier::new()
+    }
+}
+
+impl<O> FeedbackAmplifier<VecDeque<i64>, O> {
+    pub fn push_input(&mut self, value: i64) {
+        self.machine.push_input(value);
+    }
+}
+
+impl<I: InputDevice, O: OutputDevice> FeedbackAmplifier<I, O> {
+    pub fn with_devices(input: I, output: O) -> FeedbackAmplifier<I, O> {
+        FeedbackAmplifier {
+            machine: CachedMachine::with_devices(PROGRAM.to_vec(), input, output),
+            native: true,
+        }
+    }
+
+    pub fn machine(&self) -> &CachedMachine<I, O> {
+        &self.machine
+    }
+
+    pub fn input_mut(&mut self) -> &mut I {
+        self.machine.input_mut()
+    }
+
+    pub fn output(&self) -> &O {
+        self.machine.output()
+    }
+
+    pub fn output_mut(&mut self) -> &mut O {
+        self.machine.output_mut()
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.machine.is_halted()
+    }
+
+    /// Same contract as `CachedMachine::set_limits`.
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.machine.set_limits(limits);
+    }
+
+    /// Whether execution still uses the compiled code.
+    pub fn is_native(&self) -> bool {
+        self.native
+    }
+
+    /// Writes to memory. Changing the program's code hands execution to the
+    /// interpreter.
+    pub fn poke(&mut self, address: usize, value: i64) {
+        if is_code(address) {
+            self.native = false;
+        }
+        self.machine.write(address, value);
+    }
+
+    /// Same contract as `Machine::run`.
+    pub fn run(&mut self, mode: RunMode) -> Result<ProgramResult, IntcodeError> {
+        loop {
+            if !self.native {
+                return self.machine.run(mode);
+            }
+            match self.advance()? {
+                OperationResult::Output(value) if mode == RunMode::UntilOutput => {
+                    return Ok(ProgramResult::Output(value))
+                }
+                OperationResult::NeedsInput if mode == RunMode::UntilInput => {
+                    return Ok(ProgramResult::NeedsInput)
+                }
+                OperationResult::NeedsInput => {
+                    let pc = self.machine.program_counter();
+                    return Err(IntcodeError::InputExhausted {
+                        pc,
+                        instruction: self.machine.read(pc),
+                    });
+                }
+                OperationResult::Finished => return Ok(ProgramResult::Finished),
+                _ => (),
+            }
+        }
+    }
+
+    /// Runs compiled code up to the next output, backward jump or hand-over,
+    /// or else a single instruction on the interpreter.
+    pub fn advance(&mut self) -> Result<OperationResult, IntcodeError> {
+        if !self.native {
+            return self.machine.perform_operation();
+        }
+        match self.execute() {
+            Some(result) => Ok(result),
+            None => self.machine.perform_operation(),
+        }
+    }
+
+    /// Runs compiled code from the current pc. `None` leaves the instruction
+    /// at the pc to the interpreter.
+    #[allow(clippy::never_loop)] // unless a block leads into a later one
+    fn execute(&mut self) -> Option<OperationResult> {
+        let m = &mut self.machine;
+        loop {
+            match m.program_counter() {
+                0 => {
+                    // 0000: 3,26                     IN [26]
+                    if m.limit_reached() {
+                        m.set_program_counter(0);
+                        return None;
+                    }
+                    let d = 26;
+                    let value = match m.input_mut().read() {
+                        Some(value) => value,
+                        None => {
+                            m.set_program_counter(0);
+                        return None;
+                        }
+                    };
+                    m.write(d, value);
+                    m.count_step();
+                    // 0002: 1001,26,-4,26            ADD [26], #-4, [26]
+                    if m.limit_reached() {
+                        m.set_program_counter(2);
+                        return None;
+                    }
+                    let a = m.read(26);
+                    let b = -4;
+                    let d = 26;
+                    match i64::checked_add(a, b) {
+                        Some(value) => m.write(d, value),
+                        None => {
+                            m.set_program_counter(2);
+                            return None;
+                        }
+                    }
+                    m.count_step();
+                    m.set_program_counter(6);
+                }
+                6 => {
+                    // 0006: 3,27                     IN [27]
+                    if m.limit_reached() {
+                        m.set_program_counter(6);
+                        return None;
+                    }
+                    let d = 27;
+                    let value = match m.input_mut().read() {
+                        Some(value) => value,
+                        None => {
+                            m.set_program_counter(6);
+                        return None;
+                        }
+                    };
+                    m.write(d, value);
+                    m.count_step();
+                    // 0008: 1002,27,2,27             MUL [27], #2, [27]
+                    if m.limit_reached() {
+                        m.set_program_counter(8);
+                        return None;
+                    }
+                    let a = m.read(27);
+                    let b = 2;
+                    let d = 27;
+                    match i64::checked_mul(a, b) {
+                        Some(value) => m.write(d, value),
+                        None => {
+                            m.set_program_counter(8);
+                            return None;
+                        }
+                    }
+                    m.count_step();
+                    // 0012: 1,27,26,27               ADD [27], [26], [27]
+                    if m.limit_reached() {
+                        m.set_program_counter(12);
+                        return None;
+                    }
+                    let a = m.read(27);
+                    let b = m.read(26);
+                    let d = 27;
+                    match i64::checked_add(a, b) {
+                        Some(value) => m.write(d, value),
+                        None => {
+                            m.set_program_counter(12);
+                            return None;
+                        }
+                    }
+                    m.count_step();
+                    // 0016: 4,27                     OUT [27]
+                    if m.limit_reached() {
+                        m.set_program_counter(16);
+                        return None;
+                    }
+                    let a = m.read(27);
+                    m.output_mut().write(a);
+                    m.count_step();
+                    m.set_program_counter(18);
+                    return Some(OperationResult::Output(a));
+                }
+                18 => {
+                    // 0018: 1001,28,-1,28            ADD [28], #-1, [28]
+                    if m.limit_reached() {
+                        m.set_program_counter(18);
+                        return None;
+                    }
+                    let a = m.read(28);
+                    let b = -1;
+                    let d = 28;
+                    match i64::checked_add(a, b) {
+                        Some(value) => m.write(d, value),
+                        None => {
+                            m.set_program_counter(18);
+                            return None;
+                        }
+                    }
+                    m.count_step();
+                    // 0022: 1005,28,6                JNZ [28], #6
+                    if m.limit_reached() {
+                        m.set_program_counter(22);
+                        return None;
+                    }
+                    let a = m.read(28);
+                    if a != 0 {
+                        m.count_step();
+                        m.set_program_counter(6);
+                        return Some(OperationResult::Continue);
+                    }
+                    m.count_step();
+                    m.set_program_counter(25);
+                }
+                25 => {
+                    // 0025: 99                       HLT
+                    if m.limit_reached() {
+                        m.set_program_counter(25);
+                        return None;
+                    }
+                    m.set_program_counter(25);
+                    return None;
+                }
+                _ => return None,
+            }
+        }
+    }
+}
+
+impl Node for FeedbackAmplifier {
+    fn advance(&mut self) -> Result<OperationResult, IntcodeError> {
+        FeedbackAmplifier::advance(self)
+    }
+
+    fn push_input(&mut self, value: i64) {
+        FeedbackAmplifier::push_input(self, value);
+    }
+
+    fn take_output(&mut self) -> Vec<i64> {
+        std::mem::take(self.output_mut())
+    }
+
+    fn is_halted(&self) -> bool {
+        FeedbackAmplifier::is_halted(self)
+    }
+
+    fn steps(&self) -> u64 {
+        self.machine.steps()
+    }
+}
@@ -0,0 +1,17 @@
+// Programs translated by `intcode compile`, checked in so tests can run them.
+// Regenerate them with the commands in `compile::tests` when the generator
+// changes. Generated code comes with its whole API, and the tests only use
+// part of it.
+
+#[rustfmt::skip]
+#[allow(dead_code)]
+pub mod feedback_amplifier;
+#[rustfmt::skip]
+#[allow(dead_code)]
+pub mod quine;
+#[rustfmt::skip]
+#[allow(dead_code)]
+pub mod self_modifying;
+#[rustfmt::skip]
+#[allow(dead_code)]
+pub mod spinner;
@@ -0,0 +1,244 @@
+// Generated by `intcode compile`, do not edit.
+
+use std::collections::VecDeque;
+
+use crate::intcode::fast::CachedMachine;
+use crate::intcode::limit::Limits;
+use crate::intcode::network::Node;
+use crate::intcode::{
+    InputDevice, IntcodeError, OperationResult, OutputDevice, ProgramResult, RunMode,
+};
+
+const PROGRAM: [i64; 16] = [
+    109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101,
+    1006, 101, 0, 99,
+];
+
+fn is_code(address: usize) -> bool {
+    matches!(address, 0..=15)
+}
+
+pub struct Quine<I = VecDeque<i64>, O = Vec<i64>> {
+    machine: CachedMachine<I, O>,
+    native: bool,
+}
+
+impl Quine {
+    pub fn new() -> Quine {
+        Quine::with_devices(VecDeque::new(), vec![])
+    }
+}
+
+impl Default for Quine {
+    fn default() -> Quine {
+        Quine::new()
+    }
+}
+
+impl<O> Quine<VecDeque<i64>, O> {
+    pub fn push_input(&mut self, value: i64) {
+        self.machine.push_input(value);
+    }
+}
+
+impl<I: InputDevice, O: OutputDevice> Quine<I, O> {
+    pub fn with_devices(input: I, output: O) -> Quine<I, O> {
+        Quine {
+            machine: CachedMachine::with_devices(PROGRAM.to_vec(), input, output),
+            native: true,
+        }
+    }
+
+    pub fn machine(&self) -> &CachedMachine<I, O> {
+        &self.machine
+    }
+
+    pub fn input_mut(&mut self) -> &mut I {
+        self.machine.input_mut()
+    }
+
+    pub fn output(&self) -> &O {
+        self.machine.output()
+    }
+
+    pub fn output_mut(&mut self) -> &mut O {
+        self.machine.output_mut()
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.machine.is_halted()
+    }
+
+    /// Same contract as `CachedMachine::set_limits`.
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.machine.set_limits(limits);
+    }
+
+    /// Whether execution still uses the compiled code.
+    pub fn is_native(&self) -> bool {
+        self.native
+    }
+
+    /// Writes to memory. Changing the program's code hands execution to the
+    /// interpreter.
+    pub fn poke(&mut self, address: usize, value: i64) {
+        if is_code(address) {
+            self.native = false;
+        }
+        self.machine.write(address, value);
+    }
+
+    /// Same contract as `Machine::run`.
+    pub fn run(&mut self, mode: RunMode) -> Result<ProgramResult, IntcodeError> {
+        loop {
+            if !self.native {
+                return self.machine.run(mode);
+            }
+            match self.advance()? {
+                OperationResult::Output(value) if mode == RunMode::UntilOutput => {
+                    return Ok(ProgramResult::Output(value))
+                }
+                OperationResult::NeedsInput if mode == RunMode::UntilInput => {
+                    return Ok(ProgramResult::NeedsInput)
+                }
+                OperationResult::NeedsInput => {
+                    let pc = self.machine.program_counter();
+                    return Err(IntcodeError::InputExhausted {
+                        pc,
+                        instruction: self.machine.read(pc),
+                    });
+                }
+                OperationResult::Finished => return Ok(ProgramResult::Finished),
+                _ => (),
+            }
+        }
+    }
+
+    /// Runs compiled code up to the next output, backward jump or hand-over,
+    /// or else a single instruction on the interpreter.
+    pub fn advance(&mut self) -> Result<OperationResult, IntcodeError> {
+        if !self.native {
+            return self.machine.perform_operation();
+        }
+        match self.execute() {
+            Some(result) => Ok(result),
+            None => self.machine.perform_operation(),
+        }
+    }
+
+    /// Runs compiled code from the current pc. `None` leaves the instruction
+    /// at the pc to the interpreter.
+    #[allow(clippy::never_loop)] // unless a block leads into a later one
+    fn execute(&mut self) -> Option<OperationResult> {
+        let m = &mut self.machine;
+        loop {
+            match m.program_counter() {
+                0 => {
+                    // 0000: 109,1                    ARB #1
+                    if m.limit_reached() {
+                        m.set_program_counter(0);
+                        return None;
+                    }
+                    let a = 1;
+                    match m.relative_base().checked_add(a) {
+                        Some(base) => m.set_relative_base(base),
+                        None => {
+                            m.set_program_counter(0);
+                            return None;
+                        }
+                    }
+                    m.count_step();
+                    // 0002: 204,-1                   OUT [rb-1]
+                    if m.limit_reached() {
+                        m.set_program_counter(2);
+                        return None;
+                    }
+                    let a = match m.relative(-1) {
+                        Some(address) => m.read(address),
+                        None => {
+                            m.set_program_counter(2);
+                        return None;
+                        }
+                    };
+                    m.output_mut().write(a);
+                    m.count_step();
+                    m.set_program_counter(4);
+                    return Some(OperationResult::Output(a));
+                }
+                4 => {
+                    // 0004: 1001,100,1,100           ADD [100], #1, [100]
+                    if m.limit_reached() {
+                        m.set_program_counter(4);
+                        return None;
+                    }
+                    let a = m.read(100);
+                    let b = 1;
+                    let d = 100;
+                    match i64::checked_add(a, b) {
+                        Some(value) => m.write(d, value),
+                        None => {
+                            m.set_program_counter(4);
+                            return None;
+                        }
+                    }
+                    m.count_step();
+                    // 0008: 1008,100,16,101          EQ [100], #16, [101]
+                    if m.limit_reached() {
+                        m.set_program_counter(8);
+                        return None;
+                    }
+                    let a = m.read(100);
+                    let b = 16;
+                    let d = 101;
+                    m.write(d, (a == b) as i64);
+                    m.count_step();
+                    // 0012: 1006,101,0               JZ [101], #0
+                    if m.limit_reached() {
+                        m.set_program_counter(12);
+                        return None;
+                    }
+                    let a = m.read(101);
+                    if a == 0 {
+                        m.count_step();
+                        m.set_program_counter(0);
+                        return Some(OperationResult::Continue);
+                    }
+                    m.count_step();
+                    m.set_program_counter(15);
+                }
+                15 => {
+                    // 0015: 99                       HLT
+                    if m.limit_reached() {
+                        m.set_program_counter(15);
+                        return None;
+                    }
+                    m.set_program_counter(15);
+                    return None;
+                }
+                _ => return None,
+            }
+        }
+    }
+}
+
+impl Node for Quine {
+    fn advance(&mut self) -> Result<OperationResult, IntcodeError> {
+        Quine::advance(self)
+    }
+
+    fn push_input(&mut self, value: i64) {
+        Quine::push_input(self, value);
+    }
+
+    fn take_output(&mut self) -> Vec<i64> {
+        std::mem::take(self.output_mut())
+    }
+
+    fn is_halted(&self) -> bool {
+        Quine::is_halted(self)
+    }
+
+    fn steps(&self) -> u64 {
+        self.machine.steps()
+    }
+}
@@ -0,0 +1,251 @@
+// Generated by `intcode compile`, do not edit.
+
+use std::collections::VecDeque;
+
+use crate::intcode::fast::CachedMachine;
+use crate::intcode::limit::Limits;
+use crate::intcode::network::Node;
+use crate::intcode::{
+    InputDevice, IntcodeError, OperationResult, OutputDevice, ProgramResult, RunMode,
+};
+
+const PROGRAM: [i64; 21] = [
+    1101, 3, 4, 100, 4, 100, 1005, 101, 20, 1101, 1, 0,
+    101, 1101, 1102, 0, 0, 1105, 1, 0, 99,
+];
+
+fn is_code(address: usize) -> bool {
+    matches!(address, 0..=20)
+}
+
+pub struct SelfModifying<I = VecDeque<i64>, O = Vec<i64>> {
+    machine: CachedMachine<I, O>,
+    native: bool,
+}
+
+impl SelfModifying {
+    pub fn new() -> SelfModifying {
+        SelfModifying::with_devices(VecDeque::new(), vec![])
+    }
+}
+
+impl Default for SelfModifying {
+    fn default() -> SelfModifying {
+        SelfModifying::new()
+    }
+}
+
+impl<O> SelfModifying<VecDeque<i64>, O> {
+    pub fn push_input(&mut self, value: i64) {
+        self.machine.push_input(value);
+    }
+}
+
+impl<I: InputDevice, O: OutputDevice> SelfModifying<I, O> {
+    pub fn with_devices(input: I, output: O) -> SelfModifying<I, O> {
+        SelfModifying {
+            machine: CachedMachine::with_devices(PROGRAM.to_vec(), input, output),
+            native: true,
+        }
+    }
+
+    pub fn machine(&self) -> &CachedMachine<I, O> {
+        &self.machine
+    }
+
+    pub fn input_mut(&mut self) -> &mut I {
+        self.machine.input_mut()
+    }
+
+    pub fn output(&self) -> &O {
+        self.machine.output()
+    }
+
+    pub fn output_mut(&mut self) -> &mut O {
+        self.machine.output_mut()
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.machine.is_halted()
+    }
+
+    /// Same contract as `CachedMachine::set_limits`.
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.machine.set_limits(limits);
+    }
+
+    /// Whether execution still uses the compiled code.
+    pub fn is_native(&self) -> bool {
+        self.native
+    }
+
+    /// Writes to memory. Changing the program's code hands execution to the
+    /// interpreter.
+    pub fn poke(&mut self, address: usize, value: i64) {
+        if is_code(address) {
+            self.native = false;
+        }
+        self.machine.write(address, value);
+    }
+
+    /// Same contract as `Machine::run`.
+    pub fn run(&mut self, mode: RunMode) -> Result<ProgramResult, IntcodeError> {
+        loop {
+            if !self.native {
+                return self.machine.run(mode);
+            }
+            match self.advance()? {
+                OperationResult::Output(value) if mode == RunMode::UntilOutput => {
+                    return Ok(ProgramResult::Output(value))
+                }
+                OperationResult::NeedsInput if mode == RunMode::UntilInput => {
+                    return Ok(ProgramResult::NeedsInput)
+                }
+                OperationResult::NeedsInput => {
+                    let pc = self.machine.program_counter();
+                    return Err(IntcodeError::InputExhausted {
+                        pc,
+                        instruction: self.machine.read(pc),
+                    });
+                }
+                OperationResult::Finished => return Ok(ProgramResult::Finished),
+                _ => (),
+            }
+        }
+    }
+
+    /// Runs compiled code up to the next output, backward jump or hand-over,
+    /// or else a single instruction on the interpreter.
+    pub fn advance(&mut self) -> Result<OperationResult, IntcodeError> {
+        if !self.native {
+            return self.machine.perform_operation();
+        }
+        match self.execute() {
+            Some(result) => Ok(result),
+            None => self.machine.perform_operation(),
+        }
+    }
+
+    /// Runs compiled code from the current pc. `None` leaves the instruction
+    /// at the pc to the interpreter.
+    #[allow(clippy::never_loop)] // unless a block leads into a later one
+    fn execute(&mut self) -> Option<OperationResult> {
+        let m = &mut self.machine;
+        loop {
+            match m.program_counter() {
+                0 => {
+                    // 0000: 1101,3,4,100             ADD #3, #4, [100]
+                    if m.limit_reached() {
+                        m.set_program_counter(0);
+                        return None;
+                    }
+                    let a = 3;
+                    let b = 4;
+                    let d = 100;
+                    match i64::checked_add(a, b) {
+                        Some(value) => m.write(d, value),
+                        None => {
+                            m.set_program_counter(0);
+                            return None;
+                        }
+                    }
+                    m.count_step();
+                    // 0004: 4,100                    OUT [100]
+                    if m.limit_reached() {
+                        m.set_program_counter(4);
+                        return None;
+                    }
+                    let a = m.read(100);
+                    m.output_mut().write(a);
+                    m.count_step();
+                    m.set_program_counter(6);
+                    return Some(OperationResult::Output(a));
+                }
+                6 => {
+                    // 0006: 1005,101,20              JNZ [101], #20
+                    if m.limit_reached() {
+                        m.set_program_counter(6);
+                        return None;
+                    }
+                    let a = m.read(101);
+                    if a != 0 {
+                        m.count_step();
+                        m.set_program_counter(20);
+                        continue;
+                    }
+                    m.count_step();
+                    m.set_program_counter(9);
+                }
+                9 => {
+                    // 0009: 1101,1,0,101             ADD #1, #0, [101]
+                    if m.limit_reached() {
+                        m.set_program_counter(9);
+                        return None;
+                    }
+                    let a = 1;
+                    let b = 0;
+                    let d = 101;
+                    match i64::checked_add(a, b) {
+                        Some(value) => m.write(d, value),
+                        None => {
+                            m.set_program_counter(9);
+                            return None;
+                        }
+                    }
+                    m.count_step();
+                    // 0013: 1101,1102,0,0            ADD #1102, #0, [0]
+                    if m.limit_reached() {
+                        m.set_program_counter(13);
+                        return None;
+                    }
+                    let a = 1102;
+                    let b = 0;
+                    let d = 0;
+                    match i64::checked_add(a, b) {
+                        Some(value) => m.write(d, value),
+                        None => {
+                            m.set_program_counter(13);
+                            return None;
+                        }
+                    }
+                    m.count_step();
+                    self.native = false;
+                    m.set_program_counter(17);
+                    return Some(OperationResult::Continue);
+                }
+                20 => {
+                    // 0020: 99                       HLT
+                    if m.limit_reached() {
+                        m.set_program_counter(20);
+                        return None;
+                    }
+                    m.set_program_counter(20);
+                    return None;
+                }
+                _ => return None,
+            }
+        }
+    }
+}
+
+impl Node for SelfModifying {
+    fn advance(&mut self) -> Result<OperationResult, IntcodeError> {
+        SelfModifying::advance(self)
+    }
+
+    fn push_input(&mut self, value: i64) {
+        SelfModifying::push_input(self, value);
+    }
+
+    fn take_output(&mut self) -> Vec<i64> {
+        std::mem::take(self.output_mut())
+    }
+
+    fn is_halted(&self) -> bool {
+        SelfModifying::is_halted(self)
+    }
+
+    fn steps(&self) -> u64 {
+        self.machine.steps()
+    }
+}
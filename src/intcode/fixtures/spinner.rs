@@ -0,0 +1,171 @@
+// Generated by `intcode compile`, do not edit.
+
+use std::collections::VecDeque;
+
+use crate::intcode::fast::CachedMachine;
+use crate::intcode::limit::Limits;
+use crate::intcode::network::Node;
+use crate::intcode::{
+    InputDevice, IntcodeError, OperationResult, OutputDevice, ProgramResult, RunMode,
+};
+
+const PROGRAM: [i64; 3] = [
+    1105, 1, 0,
+];
+
+fn is_code(address: usize) -> bool {
+    matches!(address, 0..=2)
+}
+
+pub struct Spinner<I = VecDeque<i64>, O = Vec<i64>> {
+    machine: CachedMachine<I, O>,
+    native: bool,
+}
+
+impl Spinner {
+    pub fn new() -> Spinner {
+        Spinner::with_devices(VecDeque::new(), vec![])
+    }
+}
+
+impl Default for Spinner {
+    fn default() -> Spinner {
+        Spinner::new()
+    }
+}
+
+impl<O> Spinner<VecDeque<i64>, O> {
+    pub fn push_input(&mut self, value: i64) {
+        self.machine.push_input(value);
+    }
+}
+
+impl<I: InputDevice, O: OutputDevice> Spinner<I, O> {
+    pub fn with_devices(input: I, output: O) -> Spinner<I, O> {
+        Spinner {
+            machine: CachedMachine::with_devices(PROGRAM.to_vec(), input, output),
+            native: true,
+        }
+    }
+
+    pub fn machine(&self) -> &CachedMachine<I, O> {
+        &self.machine
+    }
+
+    pub fn input_mut(&mut self) -> &mut I {
+        self.machine.input_mut()
+    }
+
+    pub fn output(&self) -> &O {
+        self.machine.output()
+    }
+
+    pub fn output_mut(&mut self) -> &mut O {
+        self.machine.output_mut()
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.machine.is_halted()
+    }
+
+    /// Same contract as `CachedMachine::set_limits`.
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.machine.set_limits(limits);
+    }
+
+    /// Whether execution still uses the compiled code.
+    pub fn is_native(&self) -> bool {
+        self.native
+    }
+
+    /// Writes to memory. Changing the program's code hands execution to the
+    /// interpreter.
+    pub fn poke(&mut self, address: usize, value: i64) {
+        if is_code(address) {
+            self.native = false;
+        }
+        self.machine.write(address, value);
+    }
+
+    /// Same contract as `Machine::run`.
+    pub fn run(&mut self, mode: RunMode) -> Result<ProgramResult, IntcodeError> {
+        loop {
+            if !self.native {
+                return self.machine.run(mode);
+            }
+            match self.advance()? {
+                OperationResult::Output(value) if mode == RunMode::UntilOutput => {
+                    return Ok(ProgramResult::Output(value))
+                }
+                OperationResult::NeedsInput if mode == RunMode::UntilInput => {
+                    return Ok(ProgramResult::NeedsInput)
+                }
+                OperationResult::NeedsInput => {
+                    let pc = self.machine.program_counter();
+                    return Err(IntcodeError::InputExhausted {
+                        pc,
+                        instruction: self.machine.read(pc),
+                    });
+                }
+                OperationResult::Finished => return Ok(ProgramResult::Finished),
+                _ => (),
+            }
+        }
+    }
+
+    /// Runs compiled code up to the next output, backward jump or hand-over,
+    /// or else a single instruction on the interpreter.
+    pub fn advance(&mut self) -> Result<OperationResult, IntcodeError> {
+        if !self.native {
+            return self.machine.perform_operation();
+        }
+        match self.execute() {
+            Some(result) => Ok(result),
+            None => self.machine.perform_operation(),
+        }
+    }
+
+    /// Runs compiled code from the current pc. `None` leaves the instruction
+    /// at the pc to the interpreter.
+    #[allow(clippy::never_loop)] // unless a block leads into a later one
+    fn execute(&mut self) -> Option<OperationResult> {
+        let m = &mut self.machine;
+        loop {
+            match m.program_counter() {
+                0 => {
+                    // 0000: 1105,1,0                 JNZ #1, #0
+                    if m.limit_reached() {
+                        m.set_program_counter(0);
+                        return None;
+                    }
+                    m.count_step();
+                    m.set_program_counter(0);
+                    return Some(OperationResult::Continue);
+                }
+                _ => return None,
+            }
+        }
+    }
+}
+
+impl Node for Spinner {
+    fn advance(&mut self) -> Result<OperationResult, IntcodeError> {
+        Spinner::advance(self)
+    }
+
+    fn push_input(&mut self, value: i64) {
+        Spinner::push_input(self, value);
+    }
+
+    fn take_output(&mut self) -> Vec<i64> {
+        std::mem::take(self.output_mut())
+    }
+
+    fn is_halted(&self) -> bool {
+        Spinner::is_halted(self)
+    }
+
+    fn steps(&self) -> u64 {
+        self.machine.steps()
+    }
+}
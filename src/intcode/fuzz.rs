@@ -0,0 +1,447 @@
+// Differential fuzzing of the interpreters. Random and mutated programs run
+// on every engine under an instruction budget; a panic or any disagreement
+// about output, memory, registers or errors is a bug. Failing cases are
+// shrunk and reported; with INTCODE_FUZZ_SAVE=1 they are also appended to
+// `fixtures/fuzz.txt`, which is replayed on every test run. The days all run
+// `Machine` (day7 through `Network`), so it stands in for them. Set
+// INTCODE_FUZZ_CASES and INTCODE_FUZZ_SEED to fuzz for longer.
+
+use std::cell::Cell;
+use std::collections::VecDeque;
+use std::env;
+use std::fmt;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Once;
+
+use super::fast::CachedMachine;
+use super::word::Word;
+use super::{parse_program, IntcodeError, Machine, OperationResult};
+
+/// Instructions each engine may execute per case.
+const BUDGET: u64 = 2_000;
+/// Cases per test run unless INTCODE_FUZZ_CASES says otherwise.
+const CASES: u64 = 3_000;
+/// Cells past the end of the program compared after a run.
+const SLACK: usize = 16;
+const FIXTURES: &str = "src/intcode/fixtures/fuzz.txt";
+
+/// Values that tend to hit edge cases: signs, limits and the boundary
+/// between `CachedMachine`'s flat and paged memory.
+const INTERESTING: &[i64] = &[
+    0,
+    1,
+    -1,
+    99,
+    i64::MAX,
+    i64::MIN,
+    i64::MAX / 2 + 1,
+    (1 << 20) - 2,
+    1 << 20,
+    1 << 32,
+];
+
+/// Known-good programs to mutate, from the puzzle descriptions.
+const SEEDS: &[&str] = &[
+    "1,9,10,3,2,3,11,0,99,30,40,50",
+    "3,9,8,9,10,9,4,9,99,-1,8",
+    "3,3,1105,-1,9,1101,0,0,12,4,12,99,1",
+    "3,21,1008,21,8,20,1005,20,22,107,8,21,20,1006,20,31,1106,0,36,98,0,0,1002,21,125,20,\
+     4,20,1105,1,46,104,999,1105,1,46,1101,1000,1,20,4,20,1105,1,46,98,99",
+    "3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,27,4,27,1001,28,-1,28,1005,28,6,99,0,0,5",
+    "109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99",
+    "1102,34915192,34915192,7,4,7,99,0",
+    "104,1125899906842624,99",
+];
+
+/// A program and the input queued for it.
+#[derive(Clone, PartialEq, Eq, Debug)]
+struct Case {
+    program: Vec<i64>,
+    input: Vec<i64>,
+}
+
+/// How a run ended.
+#[derive(Clone, PartialEq, Eq, Debug)]
+enum Status {
+    Halted,
+    NeedsInput,
+    OutOfBudget,
+    Failed(IntcodeError),
+    Panicked(String),
+}
+
+/// Everything engines must agree on after running a case.
+#[derive(Clone, PartialEq, Eq, Debug)]
+struct Outcome {
+    status: Status,
+    output: Vec<i64>,
+    steps: u64,
+    pc: usize,
+    relative_base: i64,
+    memory: Vec<i64>,
+}
+
+type Engine = fn(&Case) -> Outcome;
+
+const ENGINES: &[(&str, Engine)] = &[
+    ("Machine", naive),
+    ("CachedMachine", cached),
+    ("Machine<i128>", word::<i128>),
+];
+
+/// xorshift64*, plenty for picking test cases.
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Rng {
+        Rng(seed.max(1))
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    fn below(&mut self, n: u64) -> u64 {
+        self.next() % n
+    }
+
+    fn pick<T: Copy>(&mut self, items: &[T]) -> T {
+        items[self.below(items.len() as u64) as usize]
+    }
+
+    /// An instruction word, mostly with valid modes.
+    fn instruction(&mut self) -> i64 {
+        let opcode = self.pick(&[1, 2, 3, 4, 5, 6, 7, 8, 9, 99]);
+        let mut modes = 0;
+        for _ in 0..3 {
+            let mode = if self.below(20) == 0 {
+                3
+            } else {
+                self.below(3)
+            };
+            modes = modes * 10 + mode as i64;
+        }
+        modes * 100 + opcode
+    }
+
+    /// Any memory word, biased towards addresses inside a program of `len`.
+    fn word(&mut self, len: usize) -> i64 {
+        match self.below(10) {
+            0..=3 => self.instruction(),
+            4..=6 => self.below(len as u64 + 4) as i64,
+            7..=8 => self.below(21) as i64 - 10,
+            _ => self.pick(INTERESTING),
+        }
+    }
+
+    fn case(&mut self) -> Case {
+        let mut program = if self.below(2) == 0 {
+            let len = 1 + self.below(32) as usize;
+            (0..len).map(|_| self.word(len)).collect()
+        } else {
+            parse_program(self.pick(SEEDS))
+        };
+        for _ in 0..1 + self.below(3) {
+            let index = self.below(program.len() as u64 + 1) as usize;
+            let value = self.word(program.len());
+            match self.below(3) {
+                0 => program.insert(index, value),
+                1 if program.len() > 1 => {
+                    program.remove(index.min(program.len() - 1));
+                }
+                _ => {
+                    let last = program.len() - 1;
+                    program[index.min(last)] = value;
+                }
+            }
+        }
+        let input = (0..self.below(5)).map(|_| self.word(8)).collect();
+        Case { program, input }
+    }
+}
+
+fn drive(mut step: impl FnMut() -> Result<OperationResult, IntcodeError>) -> Status {
+    for _ in 0..BUDGET {
+        match step() {
+            Ok(OperationResult::Finished) => return Status::Halted,
+            Ok(OperationResult::NeedsInput) => return Status::NeedsInput,
+            Ok(_) => {}
+            Err(e) => return Status::Failed(e),
+        }
+    }
+    Status::OutOfBudget
+}
+
+fn naive(case: &Case) -> Outcome {
+    let input: VecDeque<i64> = case.input.iter().copied().collect();
+    let mut machine = Machine::with_devices(case.program.clone(), input, vec![]);
+    let status = drive(|| machine.perform_operation());
+    Outcome {
+        status,
+        output: machine.output().clone(),
+        steps: machine.steps(),
+        pc: machine.program_counter(),
+        relative_base: machine.relative_base(),
+        memory: (0..case.program.len() + SLACK)
+            .map(|address| machine.memory().read(address))
+            .collect(),
+    }
+}
+
+fn cached(case: &Case) -> Outcome {
+    let input: VecDeque<i64> = case.input.iter().copied().collect();
+    let mut machine = CachedMachine::with_devices(case.program.clone(), input, vec![]);
+    let status = drive(|| machine.perform_operation());
+    Outcome {
+        status,
+        output: machine.output().clone(),
+        steps: machine.steps(),
+        pc: machine.program_counter(),
+        relative_base: machine.relative_base(),
+        memory: (0..case.program.len() + SLACK)
+            .map(|address| machine.read(address))
+            .collect(),
+    }
+}
+
+fn word<W: Word>(case: &Case) -> Outcome {
+    let narrow = |value: W| value.to_i64().unwrap_or(i64::MIN);
+    let program = case
+        .program
+        .iter()
+        .map(|&value| W::from_i64(value))
+        .collect();
+    let input: VecDeque<W> = case.input.iter().map(|&value| W::from_i64(value)).collect();
+    let mut machine = Machine::with_devices(program, input, vec![]);
+    let status = drive(|| {
+        machine.perform_operation().map(|result| match result {
+            OperationResult::NeedsInput => OperationResult::NeedsInput,
+            OperationResult::Finished => OperationResult::Finished,
+            _ => OperationResult::Continue,
+        })
+    });
+    Outcome {
+        status,
+        output: machine.output().iter().cloned().map(narrow).collect(),
+        steps: machine.steps(),
+        pc: machine.program_counter(),
+        relative_base: machine.relative_base(),
+        memory: (0..case.program.len() + SLACK)
+            .map(|address| narrow(machine.memory().read(address)))
+            .collect(),
+    }
+}
+
+thread_local! {
+    static QUIET: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Runs `engine` on `case`, turning a panic into `Status::Panicked`. Panics
+/// caught here aren't printed, other threads' still are.
+fn run(engine: Engine, case: &Case) -> Outcome {
+    static HOOK: Once = Once::new();
+    HOOK.call_once(|| {
+        let default = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            if !QUIET.with(Cell::get) {
+                default(info);
+            }
+        }));
+    });
+    QUIET.with(|quiet| quiet.set(true));
+    let result = panic::catch_unwind(AssertUnwindSafe(|| engine(case)));
+    QUIET.with(|quiet| quiet.set(false));
+    result.unwrap_or_else(|payload| {
+        let message = match payload.downcast::<String>() {
+            Ok(message) => *message,
+            Err(payload) => match payload.downcast::<&str>() {
+                Ok(message) => message.to_string(),
+                Err(_) => "unknown panic".to_string(),
+            },
+        };
+        Outcome {
+            status: Status::Panicked(message),
+            output: vec![],
+            steps: 0,
+            pc: 0,
+            relative_base: 0,
+            memory: vec![],
+        }
+    })
+}
+
+/// Runs `case` on every engine and describes the first problem found.
+fn check(case: &Case) -> Result<(), String> {
+    let outcomes: Vec<(&str, Outcome)> = ENGINES
+        .iter()
+        .map(|&(name, engine)| (name, run(engine, case)))
+        .collect();
+    for (name, outcome) in &outcomes {
+        if let Status::Panicked(message) = &outcome.status {
+            return Err(format!("{} panicked: {}", name, message));
+        }
+    }
+    let (reference, expected) = &outcomes[0];
+    for (name, outcome) in &outcomes[1..] {
+        // wider words keep going where i64 overflows
+        let overflowed = matches!(
+            expected.status,
+            Status::Failed(IntcodeError::Overflow { .. })
+        );
+        if overflowed && name.contains("i128") {
+            continue;
+        }
+        if outcome != expected {
+            return Err(format!(
+                "{} disagrees with {}:\n  {:?}\n  {:?}",
+                name, reference, outcome, expected
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Smaller values to try in place of `value`, each closer to zero.
+fn simpler(value: i64) -> Vec<i64> {
+    let mut values = vec![0, value.signum(), value / 2];
+    values.retain(|simpler| simpler.unsigned_abs() < value.unsigned_abs());
+    values.dedup();
+    values
+}
+
+/// Shrinks a case by dropping input and memory words and moving values
+/// towards zero for as long as it `fails`.
+fn minimize(mut case: Case, fails: impl Fn(&Case) -> bool) -> Case {
+    loop {
+        let mut candidates = vec![];
+        for index in (0..case.input.len()).rev() {
+            let mut candidate = case.clone();
+            candidate.input.remove(index);
+            candidates.push(candidate);
+        }
+        for index in (0..case.program.len())
+            .rev()
+            .filter(|_| case.program.len() > 1)
+        {
+            let mut candidate = case.clone();
+            candidate.program.remove(index);
+            candidates.push(candidate);
+        }
+        for (index, &value) in case.program.iter().enumerate() {
+            for simpler in simpler(value) {
+                let mut candidate = case.clone();
+                candidate.program[index] = simpler;
+                candidates.push(candidate);
+            }
+        }
+        for (index, &value) in case.input.iter().enumerate() {
+            for simpler in simpler(value) {
+                let mut candidate = case.clone();
+                candidate.input[index] = simpler;
+                candidates.push(candidate);
+            }
+        }
+        match candidates.into_iter().find(|candidate| fails(candidate)) {
+            Some(smaller) => case = smaller,
+            None => return case,
+        }
+    }
+}
+
+fn join(values: &[i64]) -> String {
+    let values: Vec<String> = values.iter().map(i64::to_string).collect();
+    values.join(",")
+}
+
+/// One fixture line: the program, then the input after a `|`.
+impl fmt::Display for Case {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} | {}", join(&self.program), join(&self.input))
+    }
+}
+
+fn fixtures() -> Vec<Case> {
+    include_str!("fixtures/fuzz.txt")
+        .lines()
+        .filter(|line| !line.trim().is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let (program, input) = line.split_once('|').expect("fixture without input");
+            Case {
+                program: parse_program(program),
+                input: parse_program(input),
+            }
+        })
+        .collect()
+}
+
+fn setting(name: &str, default: u64) -> u64 {
+    env::var(name).map_or(default, |value| {
+        value
+            .parse()
+            .unwrap_or_else(|_| panic!("{} must be a number", name))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fuzz_fixtures() {
+        for case in fixtures() {
+            if let Err(problem) = check(&case) {
+                panic!("fixture {} fails again: {}", case, problem);
+            }
+        }
+    }
+
+    #[test]
+    fn test_fuzz_engines_agree() {
+        let seed = setting("INTCODE_FUZZ_SEED", 2019);
+        let mut rng = Rng::new(seed);
+        for number in 0..setting("INTCODE_FUZZ_CASES", CASES) {
+            let case = rng.case();
+            if check(&case).is_ok() {
+                continue;
+            }
+            let case = minimize(case, |case| check(case).is_err());
+            let problem = check(&case).unwrap_err();
+            // a plain test run must not touch the source tree
+            let saved = if setting("INTCODE_FUZZ_SAVE", 0) != 0 {
+                let path = format!("{}/{}", env!("CARGO_MANIFEST_DIR"), FIXTURES);
+                let mut file = OpenOptions::new()
+                    .append(true)
+                    .open(&path)
+                    .expect("fixture file");
+                writeln!(file, "{}", case).expect("fixture file");
+                format!("saved to {}", FIXTURES)
+            } else {
+                "add it to the fixtures or rerun with INTCODE_FUZZ_SAVE=1".to_string()
+            };
+            panic!(
+                "case {} of seed {}, {}: {}\n{}",
+                number, seed, saved, case, problem
+            );
+        }
+    }
+
+    #[test]
+    fn test_minimize() {
+        let case = Case {
+            program: parse_program("1,2,3,99,4,5"),
+            input: vec![7, 5, 8],
+        };
+        let fails = |case: &Case| case.program.contains(&99) && case.input.iter().any(|&v| v >= 5);
+        let small = minimize(case, fails);
+        assert_eq!(small.program, vec![99]);
+        assert_eq!(small.input, vec![7]);
+        assert_eq!(simpler(-7), vec![0, -1, -3]);
+        assert_eq!(simpler(1), vec![0]);
+        assert_eq!(simpler(0), Vec::<i64>::new());
+    }
+}
@@ -0,0 +1,130 @@
+use std::collections::VecDeque;
+
+use super::trace::MemoryWrite;
+use super::word::Word;
+use super::Machine;
+
+/// What it takes to undo one executed instruction: the registers before it
+/// ran, the single memory cell it may have overwritten and the input it may
+/// have consumed.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Undo<W = i64> {
+    pub pc: usize,
+    pub relative_base: i64,
+    pub write: Option<MemoryWrite<W>>,
+    pub input: Option<W>,
+    pub output: Option<W>,
+}
+
+/// Undo log keeping the most recent `limit` instructions.
+pub struct History<W = i64> {
+    entries: VecDeque<Undo<W>>,
+    limit: usize,
+}
+
+impl<W> History<W> {
+    pub fn new(limit: usize) -> History<W> {
+        History {
+            entries: VecDeque::new(),
+            limit,
+        }
+    }
+
+    /// Records `undo`, dropping the oldest entry once `limit` are kept. A
+    /// limit of 0 keeps nothing.
+    pub fn push(&mut self, undo: Undo<W>) {
+        if self.limit == 0 {
+            return;
+        }
+        if self.entries.len() >= self.limit {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(undo);
+    }
+
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+}
+
+impl<O, W: Word> Machine<VecDeque<W>, O, W> {
+    /// Reverts the most recently executed instruction, putting consumed input
+    /// back into the queue. Printed values stay with the output device, the
+    /// returned record tells the caller about them. Returns `None` once the
+    /// recorded history is used up.
+    pub fn step_back(&mut self) -> Option<Undo<W>> {
+        let undo = self.history.as_mut()?.entries.pop_back()?;
+        if let Some(write) = &undo.write {
+            self.memory.write(write.address, write.old.clone());
+        }
+        if let Some(value) = &undo.input {
+            self.input.push_front(value.clone());
+        }
+        self.program_counter = undo.pc;
+        self.relative_base = undo.relative_base;
+        self.halted = false;
+        self.steps -= 1;
+        Some(undo)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::{parse_program, RunMode};
+
+    #[test]
+    fn test_step_back_to_start() {
+        let program = parse_program("3,13,109,5,1001,13,1,13,204,8,99,0,0,0");
+        let mut machine = Machine::new(program.clone());
+        machine.record_history(100);
+        machine.push_input(41);
+        machine.run(RunMode::ToHalt).unwrap();
+        assert_eq!(machine.output(), &vec![42]);
+
+        let mut undone = vec![];
+        while let Some(undo) = machine.step_back() {
+            undone.push(undo);
+        }
+        assert_eq!(undone.len(), 5);
+        assert_eq!(undone[1].output, Some(42));
+        assert_eq!(undone[4].input, Some(41));
+        assert!((0..program.len()).all(|address| machine.memory()[address] == program[address]));
+        assert_eq!(machine.program_counter(), 0);
+        assert_eq!(machine.relative_base(), 0);
+        assert_eq!(machine.steps(), 0);
+
+        machine.run(RunMode::ToHalt).unwrap();
+        assert_eq!(machine.output(), &vec![42, 42]);
+    }
+
+    #[test]
+    fn test_history_limit() {
+        let fill = |limit| {
+            let mut history: History = History::new(limit);
+            for pc in 0..3 {
+                history.push(Undo {
+                    pc,
+                    relative_base: 0,
+                    write: None,
+                    input: None,
+                    output: None,
+                });
+            }
+            history
+        };
+        let history = fill(2);
+        assert_eq!(history.entries.len(), 2);
+        assert_eq!(history.entries[0].pc, 1);
+        assert!(fill(0).entries.is_empty());
+    }
+
+    #[test]
+    fn test_step_back_without_history() {
+        let mut machine = Machine::new(parse_program("1101,1,2,5,99,0"));
+        machine.record_history(0);
+        machine.run(RunMode::ToHalt).unwrap();
+        assert_eq!(machine.step_back(), None);
+        assert_eq!(machine.memory()[5], 3);
+    }
+}
@@ -0,0 +1,215 @@
+use std::collections::{BTreeSet, VecDeque};
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use clap::{Arg, ArgMatches};
+
+use super::snapshot::Snapshot;
+use super::word::Word;
+use super::{Machine, Memory, OperationResult};
+
+/// How far `diagnose` follows a program looking for a loop.
+const PROBE_STEPS: u64 = 10_000;
+
+/// How often, in instructions, a machine with a time limit looks at the clock.
+const CLOCK_INTERVAL: u64 = 1024;
+
+/// Bounds on how long a machine may run, see `Machine::set_limits`.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub struct Limits {
+    /// Most instructions to execute, counted from the machine's start.
+    pub steps: Option<u64>,
+    /// Longest wall-clock time to run, counted from when the limits are set.
+    pub time: Option<Duration>,
+}
+
+/// The limit a machine ran into.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Limit {
+    Steps(u64),
+    Time(Duration),
+}
+
+/// What a program that ran into a limit was doing, see `diagnose`.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum LoopHint {
+    /// It came back to the same state, so it would never have stopped.
+    Cycle { addresses: usize },
+    /// It kept going without input, output or repeating itself.
+    Busy { addresses: usize },
+}
+
+impl Limits {
+    /// When the time limit runs out if it starts now.
+    pub fn deadline(&self) -> Option<Instant> {
+        self.time.map(|time| Instant::now() + time)
+    }
+
+    /// The limit a machine that has executed `steps` instructions has run
+    /// into, looking at the clock only every so often.
+    pub fn exceeded(&self, steps: u64, deadline: Option<Instant>) -> Option<Limit> {
+        if let Some(limit) = self.steps.filter(|&limit| steps >= limit) {
+            return Some(Limit::Steps(limit));
+        }
+        let deadline = deadline?;
+        if steps.is_multiple_of(CLOCK_INTERVAL) && Instant::now() >= deadline {
+            return self.time.map(Limit::Time);
+        }
+        None
+    }
+}
+
+/// Follows the program from the given state on a scratch machine, without
+/// input, to find out whether it is stuck in a loop. Nothing the original
+/// machine can see is touched. Returns `None` if the program halts, fails or
+/// waits for input soon.
+pub fn diagnose<W: Word>(memory: &Memory<W>, pc: usize, relative_base: i64) -> Option<LoopHint> {
+    let snapshot = Snapshot {
+        memory: memory.clone(),
+        program_counter: pc,
+        relative_base,
+        halted: false,
+        steps: 0,
+        input: VecDeque::new(),
+    };
+    let mut probe = Machine::from_snapshot(snapshot, Vec::<W>::new());
+    probe.take_tracer();
+    let mut addresses = BTreeSet::new();
+    for _ in 0..PROBE_STEPS {
+        addresses.insert(probe.program_counter());
+        match probe.perform_operation() {
+            Ok(OperationResult::Continue) | Ok(OperationResult::Output(_)) => {}
+            _ => return None,
+        }
+        if probe.program_counter() == pc
+            && probe.relative_base() == relative_base
+            && probe.memory() == memory
+        {
+            return Some(LoopHint::Cycle {
+                addresses: addresses.len(),
+            });
+        }
+    }
+    Some(LoopHint::Busy {
+        addresses: addresses.len(),
+    })
+}
+
+/// `--max-steps` and `--timeout` for commands that run programs.
+pub fn args() -> [Arg<'static, 'static>; 2] {
+    [
+        Arg::with_name("max-steps")
+            .long("max-steps")
+            .takes_value(true)
+            .help("Stops a program after this many instructions"),
+        Arg::with_name("timeout")
+            .long("timeout")
+            .takes_value(true)
+            .help("Stops a program after this many seconds"),
+    ]
+}
+
+pub fn from_matches(matches: &ArgMatches) -> Limits {
+    Limits {
+        steps: matches
+            .value_of("max-steps")
+            .map(|steps| steps.parse().expect("max-steps must be a number")),
+        time: matches.value_of("timeout").map(|seconds| {
+            Duration::from_secs_f64(seconds.parse().expect("timeout must be a number"))
+        }),
+    }
+}
+
+impl fmt::Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Limit::Steps(steps) => write!(f, "step limit of {}", steps),
+            Limit::Time(time) => write!(f, "time limit of {:?}", time),
+        }
+    }
+}
+
+impl fmt::Display for LoopHint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let plural = |addresses| if addresses == 1 { "" } else { "es" };
+        match *self {
+            LoopHint::Cycle { addresses } => write!(
+                f,
+                "pc cycled through the same {} address{} with identical memory",
+                addresses,
+                plural(addresses)
+            ),
+            LoopHint::Busy { addresses } => write!(
+                f,
+                "pc stayed within {} address{} for {} steps while memory changed",
+                addresses,
+                plural(addresses),
+                PROBE_STEPS
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::{parse_program, IntcodeError, RunMode};
+
+    fn limited(program: &str, limits: Limits) -> Result<Vec<i64>, IntcodeError> {
+        let mut machine = Machine::new(parse_program(program));
+        machine.set_limits(limits);
+        machine.run(RunMode::ToHalt)?;
+        Ok(machine.output().clone())
+    }
+
+    #[test]
+    fn test_step_limit() {
+        let steps = Limits {
+            steps: Some(3),
+            time: None,
+        };
+        // exactly three instructions is fine, the limit stops a fourth
+        assert_eq!(limited("104,1,104,2,99", steps), Ok(vec![1, 2]));
+        let error = limited("104,1,104,2,104,3,99", steps).unwrap_err();
+        assert_eq!(
+            error,
+            IntcodeError::LimitExceeded {
+                pc: 6,
+                instruction: 99,
+                limit: Limit::Steps(3),
+                hint: None,
+            }
+        );
+
+        let error = limited("1105,1,3,1105,1,0", steps).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "step limit of 3 reached by 1105 at pc 3; \
+             pc cycled through the same 2 addresses with identical memory"
+        );
+        let error = limited("1001,7,1,7,1105,1,0,0", steps).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "step limit of 3 reached by 1105 at pc 4; \
+             pc stayed within 2 addresses for 10000 steps while memory changed"
+        );
+    }
+
+    #[test]
+    fn test_time_limit() {
+        let time = Limits {
+            steps: None,
+            time: Some(Duration::from_millis(20)),
+        };
+        match limited("1105,1,0", time) {
+            Err(IntcodeError::LimitExceeded {
+                pc: 0,
+                limit: Limit::Time(_),
+                hint: Some(LoopHint::Cycle { addresses: 1 }),
+                ..
+            }) => {}
+            other => panic!("unexpected {:?}", other),
+        }
+        assert_eq!(limited("104,7,99", time), Ok(vec![7]));
+    }
+}
@@ -0,0 +1,168 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::ops::{Index, IndexMut};
+
+use super::word::Word;
+
+pub const PAGE_SIZE: usize = 1024;
+
+/// `PAGE_SIZE` cells.
+type Page<W> = Box<[W]>;
+
+/// Sparse Intcode memory. Every address reads as zero until it is written,
+/// and only the pages that have actually been written are allocated.
+#[derive(Clone, Debug)]
+pub struct Memory<W = i64> {
+    pages: HashMap<usize, Page<W>>,
+    /// What unallocated cells read as.
+    zero: W,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct NegativeAddress(pub i64);
+
+impl<W: Word> Memory<W> {
+    pub fn new() -> Memory<W> {
+        Memory {
+            pages: HashMap::new(),
+            zero: W::from_i64(0),
+        }
+    }
+
+    pub fn read(&self, address: usize) -> W {
+        self[address].clone()
+    }
+
+    pub fn write(&mut self, address: usize, value: W) {
+        self[address] = value;
+    }
+
+    pub fn get(&self, address: i64) -> Result<W, NegativeAddress> {
+        Ok(self.read(to_address(address)?))
+    }
+
+    /// Allocated pages in address order, each with the address of its first cell.
+    pub fn pages(&self) -> Vec<(usize, &[W])> {
+        let mut pages: Vec<(usize, &[W])> = self
+            .pages
+            .iter()
+            .map(|(&index, page)| (index * PAGE_SIZE, &page[..]))
+            .collect();
+        pages.sort_unstable_by_key(|&(start, _)| start);
+        pages
+    }
+}
+
+pub fn to_address(value: i64) -> Result<usize, NegativeAddress> {
+    if value < 0 {
+        Err(NegativeAddress(value))
+    } else {
+        Ok(value as usize)
+    }
+}
+
+impl<W: Word> Default for Memory<W> {
+    fn default() -> Memory<W> {
+        Memory::new()
+    }
+}
+
+impl<W: Word> From<Vec<W>> for Memory<W> {
+    fn from(program: Vec<W>) -> Memory<W> {
+        let mut memory = Memory::new();
+        for (address, value) in program.into_iter().enumerate() {
+            memory.write(address, value);
+        }
+        memory
+    }
+}
+
+/// Memories are equal when every address reads the same, however many pages
+/// of zeros either has allocated.
+impl<W: Word> PartialEq for Memory<W> {
+    fn eq(&self, other: &Memory<W>) -> bool {
+        let covers = |memory: &Memory<W>, other: &Memory<W>| {
+            memory
+                .pages
+                .iter()
+                .all(|(index, page)| match other.pages.get(index) {
+                    Some(other) => page == other,
+                    None => page.iter().all(|value| *value == memory.zero),
+                })
+        };
+        covers(self, other) && covers(other, self)
+    }
+}
+
+impl<W: Word + Eq> Eq for Memory<W> {}
+
+impl<W> Index<usize> for Memory<W> {
+    type Output = W;
+
+    fn index(&self, address: usize) -> &W {
+        self.pages
+            .get(&(address / PAGE_SIZE))
+            .map_or(&self.zero, |page| &page[address % PAGE_SIZE])
+    }
+}
+
+impl<W: Clone> IndexMut<usize> for Memory<W> {
+    fn index_mut(&mut self, address: usize) -> &mut W {
+        let zero = &self.zero;
+        &mut self
+            .pages
+            .entry(address / PAGE_SIZE)
+            .or_insert_with(|| vec![zero.clone(); PAGE_SIZE].into_boxed_slice())
+            [address % PAGE_SIZE]
+    }
+}
+
+impl fmt::Display for NegativeAddress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "negative address {}", self.0)
+    }
+}
+
+impl Error for NegativeAddress {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unwritten_memory_reads_zero() {
+        let memory = Memory::from(vec![1i64, 2, 3]);
+        assert_eq!(memory[2], 3);
+        assert_eq!(memory[3], 0);
+        assert_eq!(memory.read(5 * PAGE_SIZE), 0);
+    }
+
+    #[test]
+    fn test_far_write_allocates_single_page() {
+        let mut memory: Memory = Memory::new();
+        memory.write(1_000_000_000, 42);
+        assert_eq!(memory.read(1_000_000_000), 42);
+        assert_eq!(memory.read(999_999_999), 0);
+        assert_eq!(memory.pages.len(), 1);
+    }
+
+    #[test]
+    fn test_equality_ignores_zero_pages() {
+        let mut memory = Memory::from(vec![1i64, 2]);
+        assert_eq!(memory, Memory::from(vec![1i64, 2, 0]));
+        memory.write(3 * PAGE_SIZE, 0);
+        assert_eq!(memory, Memory::from(vec![1i64, 2]));
+        memory.write(3 * PAGE_SIZE, 5);
+        assert_ne!(memory, Memory::from(vec![1i64, 2]));
+        assert_ne!(Memory::from(vec![1i64, 2]), memory);
+    }
+
+    #[test]
+    fn test_negative_address() {
+        let mut memory: Memory = Memory::new();
+        assert_eq!(memory.get(-1), Err(NegativeAddress(-1)));
+        memory.write(7, 1);
+        assert_eq!(memory.get(7), Ok(1));
+    }
+}
@@ -0,0 +1,947 @@
+use std::collections::VecDeque;
+use std::io::{stdin, Read};
+use std::time::Instant;
+
+pub mod asm;
+pub mod cfg;
+pub mod cli;
+pub mod compile;
+pub mod debugger;
+pub mod device;
+pub mod disasm;
+mod error;
+pub mod fast;
+#[cfg(test)]
+mod fixtures;
+#[cfg(test)]
+mod fuzz;
+pub mod history;
+pub mod limit;
+mod memory;
+pub mod network;
+pub mod profile;
+pub mod snapshot;
+pub mod solver;
+pub mod symbolic;
+pub mod trace;
+pub mod watch;
+pub mod word;
+
+pub use self::device::{InputDevice, OutputDevice};
+pub use self::error::IntcodeError;
+use self::history::{History, Undo};
+use self::limit::Limits;
+pub use self::memory::Memory;
+use self::memory::{to_address, NegativeAddress};
+use self::snapshot::Snapshot;
+use self::trace::{MemoryRead, MemoryWrite, Step, Tracer};
+use self::watch::{WatchHit, Watchpoint};
+use self::word::Word;
+
+pub fn read_program() -> Vec<i64> {
+    let mut s = Vec::new();
+    stdin()
+        .read_to_end(&mut s)
+        .expect("did not enter a correct string");
+
+    let input_string = std::str::from_utf8(&s).expect("invalid utf8");
+    parse_program(input_string)
+}
+
+pub fn parse_program(input: &str) -> Vec<i64> {
+    input
+        .split(',')
+        .filter_map(|value| value.trim().parse::<i64>().ok())
+        .collect()
+}
+
+/// The Intcode interpreter, generic over its input and output devices and
+/// over `W`, the type of a memory cell.
+pub struct Machine<I = VecDeque<i64>, O = Vec<i64>, W = i64> {
+    memory: Memory<W>,
+    program_counter: usize,
+    relative_base: i64,
+    input: I,
+    output: O,
+    halted: bool,
+    steps: u64,
+    tracer: Option<Box<dyn Tracer<W>>>,
+    watchpoints: Vec<Watchpoint>,
+    history: Option<History<W>>,
+    limits: Limits,
+    deadline: Option<Instant>,
+    reads: Vec<MemoryRead<W>>,
+    write: Option<MemoryWrite<W>>,
+}
+
+/// Decides when `Machine::run` hands control back to the caller.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum RunMode {
+    /// Stop after every printed value.
+    UntilOutput,
+    /// Stop as soon as the input device runs dry instead of failing.
+    UntilInput,
+    /// Keep going until the program halts.
+    ToHalt,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum ProgramResult<W = i64> {
+    Output(W),
+    NeedsInput,
+    /// A watchpoint fired; the instruction responsible has completed.
+    Watchpoint(WatchHit<W>),
+    Finished,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum OperationResult<W = i64> {
+    Output(W),
+    NeedsInput,
+    Watchpoint(WatchHit<W>),
+    Continue,
+    Finished,
+}
+
+impl Machine {
+    pub fn new(program: Vec<i64>) -> Machine {
+        Machine::with_devices(program, VecDeque::new(), vec![])
+    }
+}
+
+impl<O, W> Machine<VecDeque<W>, O, W> {
+    pub fn push_input(&mut self, value: W) {
+        self.input.push_back(value);
+    }
+}
+
+impl<I: InputDevice<W>, O: OutputDevice<W>, W: Word> Machine<I, O, W> {
+    pub fn with_devices(program: Vec<W>, input: I, output: O) -> Machine<I, O, W> {
+        Machine {
+            memory: Memory::from(program),
+            program_counter: 0,
+            relative_base: 0,
+            input,
+            output,
+            halted: false,
+            steps: 0,
+            tracer: profile::profiler().map(|profiler| Box::new(profiler) as Box<dyn Tracer<W>>),
+            watchpoints: vec![],
+            history: None,
+            limits: Limits::default(),
+            deadline: None,
+            reads: Vec::with_capacity(3),
+            write: None,
+        }
+    }
+
+    /// Resumes a machine from `snapshot`, printing to a fresh output device.
+    pub fn from_snapshot(snapshot: Snapshot<I, W>, output: O) -> Machine<I, O, W> {
+        let mut machine = Machine::with_devices(vec![], snapshot.input, output);
+        machine.memory = snapshot.memory;
+        machine.program_counter = snapshot.program_counter;
+        machine.relative_base = snapshot.relative_base;
+        machine.halted = snapshot.halted;
+        machine.steps = snapshot.steps;
+        machine
+    }
+
+    pub fn memory(&self) -> &Memory<W> {
+        &self.memory
+    }
+
+    pub fn memory_mut(&mut self) -> &mut Memory<W> {
+        &mut self.memory
+    }
+
+    pub fn output(&self) -> &O {
+        &self.output
+    }
+
+    pub fn output_mut(&mut self) -> &mut O {
+        &mut self.output
+    }
+
+    pub fn program_counter(&self) -> usize {
+        self.program_counter
+    }
+
+    pub fn relative_base(&self) -> i64 {
+        self.relative_base
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.halted
+    }
+
+    /// Number of instructions executed so far.
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    /// Hands every instruction executed from now on to `tracer`, replacing
+    /// the profiler if profiling is enabled.
+    pub fn set_tracer(&mut self, tracer: Box<dyn Tracer<W>>) {
+        self.tracer = Some(tracer);
+    }
+
+    pub fn take_tracer(&mut self) -> Option<Box<dyn Tracer<W>>> {
+        self.tracer.take()
+    }
+
+    /// Keeps an undo log of the last `limit` instructions, see `step_back`.
+    pub fn record_history(&mut self, limit: usize) {
+        self.history = Some(History::new(limit));
+    }
+
+    /// Makes `perform_operation`, and so `run`, fail with `LimitExceeded`
+    /// once the machine has executed `limits.steps` instructions in total or
+    /// `limits.time` has passed from now.
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+        self.deadline = limits.deadline();
+    }
+
+    #[cfg(test)]
+    pub fn limits(&self) -> Limits {
+        self.limits
+    }
+
+    pub fn watchpoints(&self) -> &[Watchpoint] {
+        &self.watchpoints
+    }
+
+    pub fn add_watchpoint(&mut self, watchpoint: Watchpoint) {
+        self.watchpoints.push(watchpoint);
+    }
+
+    pub fn remove_watchpoint(&mut self, watchpoint: Watchpoint) -> bool {
+        let count = self.watchpoints.len();
+        self.watchpoints.retain(|watch| *watch != watchpoint);
+        self.watchpoints.len() != count
+    }
+
+    pub fn run(&mut self, mode: RunMode) -> Result<ProgramResult<W>, IntcodeError> {
+        loop {
+            match self.perform_operation()? {
+                OperationResult::Output(value) if mode == RunMode::UntilOutput => {
+                    return Ok(ProgramResult::Output(value))
+                }
+                OperationResult::NeedsInput if mode == RunMode::UntilInput => {
+                    return Ok(ProgramResult::NeedsInput)
+                }
+                OperationResult::Watchpoint(hit) => return Ok(ProgramResult::Watchpoint(hit)),
+                OperationResult::NeedsInput => {
+                    return Err(IntcodeError::InputExhausted {
+                        pc: self.program_counter,
+                        instruction: self.instruction(),
+                    })
+                }
+                OperationResult::Finished => return Ok(ProgramResult::Finished),
+                OperationResult::Output(_) | OperationResult::Continue => continue,
+            }
+        }
+    }
+
+    pub fn snapshot(&self) -> Snapshot<I, W>
+    where
+        I: Clone,
+    {
+        Snapshot {
+            memory: self.memory.clone(),
+            program_counter: self.program_counter,
+            relative_base: self.relative_base,
+            halted: self.halted,
+            steps: self.steps,
+            input: self.input.clone(),
+        }
+    }
+
+    /// Rewinds the machine to `snapshot`. Values already printed stay in the
+    /// output device, and the undo log starts over.
+    pub fn restore(&mut self, snapshot: &Snapshot<I, W>)
+    where
+        I: Clone,
+    {
+        self.memory.clone_from(&snapshot.memory);
+        self.program_counter = snapshot.program_counter;
+        self.relative_base = snapshot.relative_base;
+        self.halted = snapshot.halted;
+        self.steps = snapshot.steps;
+        self.input.clone_from(&snapshot.input);
+        if let Some(history) = self.history.as_mut() {
+            *history = History::new(history.limit());
+        }
+    }
+
+    /// Resolves the parameter stored at `address` to the memory cell it refers to.
+    fn resolve(&self, address: usize, mode: AddressingMode) -> Result<i64, IntcodeError> {
+        let parameter = self.memory.read(address);
+        match mode {
+            AddressingMode::Indirect => self.narrow(&parameter),
+            AddressingMode::Relative => self
+                .relative_base
+                .checked_add(self.narrow(&parameter)?)
+                .ok_or_else(|| self.overflow()),
+            AddressingMode::Immediate => Ok(address as i64),
+        }
+    }
+
+    fn load(&mut self, address: usize, mode: AddressingMode) -> Result<W, IntcodeError> {
+        let source = self.resolve(address, mode)?;
+        let value = self
+            .memory
+            .get(source)
+            .map_err(|e| self.negative_address(e))?;
+        self.reads.push(MemoryRead {
+            address: source as usize,
+            value: value.clone(),
+        });
+        Ok(value)
+    }
+
+    fn store(
+        &mut self,
+        address: usize,
+        mode: AddressingMode,
+        value: W,
+    ) -> Result<(), IntcodeError> {
+        if mode == AddressingMode::Immediate {
+            return Err(IntcodeError::WriteToImmediate {
+                pc: self.program_counter,
+                instruction: self.instruction(),
+            });
+        }
+        let destination =
+            to_address(self.resolve(address, mode)?).map_err(|e| self.negative_address(e))?;
+        self.write = Some(MemoryWrite {
+            address: destination,
+            old: self.memory.read(destination),
+            new: value.clone(),
+        });
+        self.memory.write(destination, value);
+        Ok(())
+    }
+
+    fn jump_target(&self, value: &W) -> Result<usize, IntcodeError> {
+        to_address(self.narrow(value)?).map_err(|e| self.negative_address(e))
+    }
+
+    /// `value` as an address, offset or jump target, which have to fit an `i64`.
+    fn narrow(&self, value: &W) -> Result<i64, IntcodeError> {
+        value.to_i64().ok_or_else(|| self.overflow())
+    }
+
+    /// The instruction at the program counter, for error reports.
+    fn instruction(&self) -> i64 {
+        self.memory[self.program_counter].saturating_i64()
+    }
+
+    fn negative_address(&self, NegativeAddress(address): NegativeAddress) -> IntcodeError {
+        IntcodeError::NegativeAddress {
+            pc: self.program_counter,
+            instruction: self.instruction(),
+            address,
+        }
+    }
+
+    fn overflow(&self) -> IntcodeError {
+        IntcodeError::Overflow {
+            pc: self.program_counter,
+            instruction: self.instruction(),
+        }
+    }
+
+    /// Executes a single instruction. An input instruction without input
+    /// leaves the machine untouched and reports `NeedsInput`.
+    pub fn perform_operation(&mut self) -> Result<OperationResult<W>, IntcodeError> {
+        if self.halted {
+            return Ok(OperationResult::Finished);
+        }
+
+        let pc = self.program_counter;
+        let relative_base = self.relative_base;
+        let instruction = self.instruction();
+        if self.memory[pc].to_i64().is_none() {
+            // report the nearest i64, which is all the error can hold
+            return Err(IntcodeError::InvalidOpcode {
+                pc,
+                value: instruction,
+            });
+        }
+        if let Some(limit) = self.limits.exceeded(self.steps, self.deadline) {
+            return Err(IntcodeError::LimitExceeded {
+                pc,
+                instruction,
+                limit,
+                hint: limit::diagnose(&self.memory, pc, relative_base),
+            });
+        }
+        self.reads.clear();
+        self.write = None;
+        let result = self.execute(pc, instruction)?;
+        if result == OperationResult::NeedsInput {
+            return Ok(result);
+        }
+
+        self.steps += 1;
+        if let Some(history) = self.history.as_mut() {
+            let is_input = matches!(parse_opcode(instruction), Some(Operation::Input { .. }));
+            history.push(Undo {
+                pc,
+                relative_base,
+                write: self.write.clone(),
+                input: self
+                    .write
+                    .as_ref()
+                    .filter(|_| is_input)
+                    .map(|write| write.new.clone()),
+                output: match &result {
+                    OperationResult::Output(value) => Some(value.clone()),
+                    _ => None,
+                },
+            });
+        }
+        if let Some(tracer) = self.tracer.as_mut() {
+            tracer.record(&Step {
+                number: self.steps,
+                pc,
+                instruction,
+                operation: parse_opcode(instruction).expect("executed instruction decodes"),
+                reads: self.reads.clone(),
+                write: self.write.clone(),
+                relative_base: self.relative_base,
+                next_pc: self.program_counter,
+            });
+        }
+        if !self.watchpoints.is_empty() {
+            let write = self.write.as_ref();
+            let hit = watch::check(&self.watchpoints, pc, instruction, &self.reads, write);
+            if let Some(hit) = hit {
+                return Ok(OperationResult::Watchpoint(hit));
+            }
+        }
+        Ok(result)
+    }
+
+    fn execute(&mut self, pc: usize, opcode: i64) -> Result<OperationResult<W>, IntcodeError> {
+        match parse_opcode(opcode) {
+            Some(Operation::Addition {
+                summand1_mode,
+                summand2_mode,
+                destination_mode,
+            }) => {
+                let summand1 = self.load(pc + 1, summand1_mode)?;
+                let summand2 = self.load(pc + 2, summand2_mode)?;
+                let sum = summand1
+                    .checked_add(&summand2)
+                    .ok_or_else(|| self.overflow())?;
+                self.store(pc + 3, destination_mode, sum)?;
+                self.program_counter = pc + 4;
+            }
+            Some(Operation::Multiplication {
+                factor1_mode,
+                factor2_mode,
+                destination_mode,
+            }) => {
+                let factor1 = self.load(pc + 1, factor1_mode)?;
+                let factor2 = self.load(pc + 2, factor2_mode)?;
+                let product = factor1
+                    .checked_mul(&factor2)
+                    .ok_or_else(|| self.overflow())?;
+                self.store(pc + 3, destination_mode, product)?;
+                self.program_counter = pc + 4;
+            }
+            Some(Operation::JumpIfTrue {
+                condition_mode,
+                value_mode,
+            }) => {
+                let condition = self.load(pc + 1, condition_mode)?;
+                let value = self.load(pc + 2, value_mode)?;
+                self.program_counter = if !condition.is_zero() {
+                    self.jump_target(&value)?
+                } else {
+                    pc + 3
+                };
+            }
+            Some(Operation::JumpIfFalse {
+                condition_mode,
+                value_mode,
+            }) => {
+                let condition = self.load(pc + 1, condition_mode)?;
+                let value = self.load(pc + 2, value_mode)?;
+                self.program_counter = if condition.is_zero() {
+                    self.jump_target(&value)?
+                } else {
+                    pc + 3
+                };
+            }
+            Some(Operation::LessThan {
+                left_parameter_mode,
+                right_parameter_mode,
+                destination_mode,
+            }) => {
+                let left_parameter = self.load(pc + 1, left_parameter_mode)?;
+                let right_parameter = self.load(pc + 2, right_parameter_mode)?;
+                self.store(
+                    pc + 3,
+                    destination_mode,
+                    W::from_i64((left_parameter < right_parameter) as i64),
+                )?;
+                self.program_counter = pc + 4;
+            }
+            Some(Operation::Equals {
+                left_parameter_mode,
+                right_parameter_mode,
+                destination_mode,
+            }) => {
+                let left_parameter = self.load(pc + 1, left_parameter_mode)?;
+                let right_parameter = self.load(pc + 2, right_parameter_mode)?;
+                self.store(
+                    pc + 3,
+                    destination_mode,
+                    W::from_i64((left_parameter == right_parameter) as i64),
+                )?;
+                self.program_counter = pc + 4;
+            }
+            Some(Operation::Input { destination_mode }) => {
+                let value = match self.input.read() {
+                    Some(value) => value,
+                    None => return Ok(OperationResult::NeedsInput),
+                };
+                self.store(pc + 1, destination_mode, value)?;
+                self.program_counter = pc + 2;
+            }
+            Some(Operation::Print { value_mode }) => {
+                let value = self.load(pc + 1, value_mode)?;
+                self.output.write(value.clone());
+                self.program_counter = pc + 2;
+                return Ok(OperationResult::Output(value));
+            }
+            Some(Operation::AdjustRelativeBase { offset_mode }) => {
+                let offset = self.load(pc + 1, offset_mode)?;
+                let offset = self.narrow(&offset)?;
+                self.relative_base = self
+                    .relative_base
+                    .checked_add(offset)
+                    .ok_or_else(|| self.overflow())?;
+                self.program_counter = pc + 2;
+            }
+            Some(Operation::Exit) => {
+                self.halted = true;
+                return Ok(OperationResult::Finished);
+            }
+            None => return Err(decode_error(pc, opcode)),
+        };
+        Ok(OperationResult::Continue)
+    }
+}
+
+pub fn parse_opcode(opcode: i64) -> Option<Operation> {
+    let operation_str = opcode % 100;
+    let op1_mode_str = (opcode / 100) % 10;
+    let op2_mode_str = (opcode / 1000) % 10;
+    let op3_mode_str = (opcode / 10000) % 10;
+
+    match operation_str {
+        1 => Some(Operation::Addition {
+            summand1_mode: mode_str_to_addressing_mode(op1_mode_str)?,
+            summand2_mode: mode_str_to_addressing_mode(op2_mode_str)?,
+            destination_mode: mode_str_to_addressing_mode(op3_mode_str)?,
+        }),
+        2 => Some(Operation::Multiplication {
+            factor1_mode: mode_str_to_addressing_mode(op1_mode_str)?,
+            factor2_mode: mode_str_to_addressing_mode(op2_mode_str)?,
+            destination_mode: mode_str_to_addressing_mode(op3_mode_str)?,
+        }),
+        3 => Some(Operation::Input {
+            destination_mode: mode_str_to_addressing_mode(op1_mode_str)?,
+        }),
+        4 => Some(Operation::Print {
+            value_mode: mode_str_to_addressing_mode(op1_mode_str)?,
+        }),
+        5 => Some(Operation::JumpIfTrue {
+            condition_mode: mode_str_to_addressing_mode(op1_mode_str)?,
+            value_mode: mode_str_to_addressing_mode(op2_mode_str)?,
+        }),
+        6 => Some(Operation::JumpIfFalse {
+            condition_mode: mode_str_to_addressing_mode(op1_mode_str)?,
+            value_mode: mode_str_to_addressing_mode(op2_mode_str)?,
+        }),
+        7 => Some(Operation::LessThan {
+            left_parameter_mode: mode_str_to_addressing_mode(op1_mode_str)?,
+            right_parameter_mode: mode_str_to_addressing_mode(op2_mode_str)?,
+            destination_mode: mode_str_to_addressing_mode(op3_mode_str)?,
+        }),
+        8 => Some(Operation::Equals {
+            left_parameter_mode: mode_str_to_addressing_mode(op1_mode_str)?,
+            right_parameter_mode: mode_str_to_addressing_mode(op2_mode_str)?,
+            destination_mode: mode_str_to_addressing_mode(op3_mode_str)?,
+        }),
+        9 => Some(Operation::AdjustRelativeBase {
+            offset_mode: mode_str_to_addressing_mode(op1_mode_str)?,
+        }),
+        99 => Some(Operation::Exit),
+        _ => None,
+    }
+}
+
+/// Explains why `parse_opcode` rejected the instruction at `pc`.
+fn decode_error(pc: usize, instruction: i64) -> IntcodeError {
+    if parse_opcode(instruction % 100).is_none() {
+        return IntcodeError::InvalidOpcode {
+            pc,
+            value: instruction,
+        };
+    }
+    let mut modes = instruction / 100;
+    for parameter in 1..=3 {
+        if mode_str_to_addressing_mode(modes % 10).is_none() {
+            return IntcodeError::InvalidMode {
+                pc,
+                instruction,
+                parameter,
+            };
+        }
+        modes /= 10;
+    }
+    unreachable!("{} decodes fine", instruction)
+}
+
+fn mode_str_to_addressing_mode(c: i64) -> Option<AddressingMode> {
+    match c {
+        0 => Some(AddressingMode::Indirect),
+        1 => Some(AddressingMode::Immediate),
+        2 => Some(AddressingMode::Relative),
+        _ => None,
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Operation {
+    Addition {
+        summand1_mode: AddressingMode,
+        summand2_mode: AddressingMode,
+        destination_mode: AddressingMode,
+    },
+    Multiplication {
+        factor1_mode: AddressingMode,
+        factor2_mode: AddressingMode,
+        destination_mode: AddressingMode,
+    },
+    Input {
+        destination_mode: AddressingMode,
+    },
+    Print {
+        value_mode: AddressingMode,
+    },
+    JumpIfTrue {
+        condition_mode: AddressingMode,
+        value_mode: AddressingMode,
+    },
+    JumpIfFalse {
+        condition_mode: AddressingMode,
+        value_mode: AddressingMode,
+    },
+    LessThan {
+        left_parameter_mode: AddressingMode,
+        right_parameter_mode: AddressingMode,
+        destination_mode: AddressingMode,
+    },
+    Equals {
+        left_parameter_mode: AddressingMode,
+        right_parameter_mode: AddressingMode,
+        destination_mode: AddressingMode,
+    },
+    AdjustRelativeBase {
+        offset_mode: AddressingMode,
+    },
+    Exit,
+}
+
+impl Operation {
+    pub fn mnemonic(&self) -> &'static str {
+        match self {
+            Operation::Addition { .. } => "ADD",
+            Operation::Multiplication { .. } => "MUL",
+            Operation::Input { .. } => "IN",
+            Operation::Print { .. } => "OUT",
+            Operation::JumpIfTrue { .. } => "JNZ",
+            Operation::JumpIfFalse { .. } => "JZ",
+            Operation::LessThan { .. } => "LT",
+            Operation::Equals { .. } => "EQ",
+            Operation::AdjustRelativeBase { .. } => "ARB",
+            Operation::Exit => "HLT",
+        }
+    }
+
+    /// Addressing modes of the parameters following the opcode, in order.
+    pub fn parameter_modes(&self) -> Vec<AddressingMode> {
+        match *self {
+            Operation::Addition {
+                summand1_mode: a,
+                summand2_mode: b,
+                destination_mode: c,
+            }
+            | Operation::Multiplication {
+                factor1_mode: a,
+                factor2_mode: b,
+                destination_mode: c,
+            }
+            | Operation::LessThan {
+                left_parameter_mode: a,
+                right_parameter_mode: b,
+                destination_mode: c,
+            }
+            | Operation::Equals {
+                left_parameter_mode: a,
+                right_parameter_mode: b,
+                destination_mode: c,
+            } => vec![a, b, c],
+            Operation::JumpIfTrue {
+                condition_mode: a,
+                value_mode: b,
+            }
+            | Operation::JumpIfFalse {
+                condition_mode: a,
+                value_mode: b,
+            } => vec![a, b],
+            Operation::Input {
+                destination_mode: a,
+            }
+            | Operation::Print { value_mode: a }
+            | Operation::AdjustRelativeBase { offset_mode: a } => vec![a],
+            Operation::Exit => vec![],
+        }
+    }
+
+    /// Number of memory words the instruction occupies, opcode included.
+    pub fn len(&self) -> usize {
+        self.parameter_modes().len() + 1
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum AddressingMode {
+    Indirect,
+    Immediate,
+    Relative,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_opcode_example1() {
+        assert_eq!(
+            parse_opcode(1002),
+            Some(Operation::Multiplication {
+                factor1_mode: AddressingMode::Indirect,
+                factor2_mode: AddressingMode::Immediate,
+                destination_mode: AddressingMode::Indirect,
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_opcode_relative() {
+        assert_eq!(
+            parse_opcode(21201),
+            Some(Operation::Addition {
+                summand1_mode: AddressingMode::Relative,
+                summand2_mode: AddressingMode::Immediate,
+                destination_mode: AddressingMode::Relative,
+            })
+        );
+        assert_eq!(
+            parse_opcode(209),
+            Some(Operation::AdjustRelativeBase {
+                offset_mode: AddressingMode::Relative
+            })
+        );
+        assert_eq!(parse_opcode(301), None);
+    }
+
+    #[test]
+    fn test_run_day2_example() {
+        let mut machine = Machine::new(vec![1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50]);
+        machine.run(RunMode::ToHalt).unwrap();
+        assert_eq!(machine.memory()[0], 3500);
+    }
+
+    #[test]
+    fn test_run_day5_equal_to_8() {
+        let program = parse_program("3,9,8,9,10,9,4,9,99,-1,8");
+        for &(input, expected) in &[(8, 1), (7, 0)] {
+            let mut machine = Machine::new(program.clone());
+            machine.push_input(input);
+            machine.run(RunMode::ToHalt).unwrap();
+            assert_eq!(machine.output(), &vec![expected]);
+        }
+    }
+
+    #[test]
+    fn test_run_relative_base() {
+        let mut machine = Machine::new(parse_program("109,5,204,1,99,0,42"));
+        machine.run(RunMode::ToHalt).unwrap();
+        assert_eq!(machine.output(), &vec![42]);
+
+        let mut machine = Machine::new(parse_program("109,9,21101,3,4,0,204,0,99,0"));
+        machine.run(RunMode::ToHalt).unwrap();
+        assert_eq!(machine.output(), &vec![7]);
+    }
+
+    #[test]
+    fn test_run_quine() {
+        let program = parse_program("109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99");
+        let mut machine = Machine::new(program.clone());
+        machine.run(RunMode::ToHalt).unwrap();
+        assert_eq!(machine.output(), &program);
+    }
+
+    #[test]
+    fn test_run_errors() {
+        let run = |program: &str| Machine::new(parse_program(program)).run(RunMode::ToHalt);
+        assert_eq!(
+            run("1,0,0,0,42"),
+            Err(IntcodeError::InvalidOpcode { pc: 4, value: 42 })
+        );
+        assert_eq!(
+            run("1,0,0,0,3001,0,0,0"),
+            Err(IntcodeError::InvalidMode {
+                pc: 4,
+                instruction: 3001,
+                parameter: 2
+            })
+        );
+        assert_eq!(
+            run("4,-1,99"),
+            Err(IntcodeError::NegativeAddress {
+                pc: 0,
+                instruction: 4,
+                address: -1
+            })
+        );
+        assert_eq!(
+            run("1105,1,-3"),
+            Err(IntcodeError::NegativeAddress {
+                pc: 0,
+                instruction: 1105,
+                address: -3
+            })
+        );
+        assert_eq!(
+            run("104,1,3,0,99"),
+            Err(IntcodeError::InputExhausted {
+                pc: 2,
+                instruction: 3
+            })
+        );
+        assert_eq!(
+            run("11101,1,1,0,99"),
+            Err(IntcodeError::WriteToImmediate {
+                pc: 0,
+                instruction: 11101
+            })
+        );
+        assert_eq!(
+            run("1102,4611686018427387904,2,0,99"),
+            Err(IntcodeError::Overflow {
+                pc: 0,
+                instruction: 1102
+            })
+        );
+    }
+
+    #[test]
+    fn test_run_large_immediate_output() {
+        let mut machine = Machine::new(parse_program("104,1125899906842624,99"));
+        machine.run(RunMode::ToHalt).unwrap();
+        assert_eq!(machine.output(), &vec![1125899906842624]);
+    }
+
+    #[test]
+    fn test_run_until_output() {
+        let mut machine = Machine::new(parse_program("4,0,4,0,99"));
+        let mut run = || machine.run(RunMode::UntilOutput).unwrap();
+        assert_eq!(run(), ProgramResult::Output(4));
+        assert_eq!(run(), ProgramResult::Output(4));
+        assert_eq!(run(), ProgramResult::Finished);
+        assert_eq!(run(), ProgramResult::Finished);
+    }
+
+    #[test]
+    fn test_run_until_input() {
+        let mut machine = Machine::new(parse_program("3,0,4,0,3,0,4,0,99"));
+        assert_eq!(
+            machine.run(RunMode::UntilInput).unwrap(),
+            ProgramResult::NeedsInput
+        );
+        machine.push_input(1);
+        assert_eq!(
+            machine.run(RunMode::UntilInput).unwrap(),
+            ProgramResult::NeedsInput
+        );
+        assert!(machine.run(RunMode::ToHalt).is_err());
+        machine.push_input(2);
+        assert_eq!(
+            machine.run(RunMode::UntilInput).unwrap(),
+            ProgramResult::Finished
+        );
+        assert_eq!(machine.output(), &vec![1, 2]);
+    }
+
+    #[test]
+    fn test_run_stops_at_watchpoints() {
+        use super::watch::{Access, WatchKind};
+
+        let mut machine = Machine::new(parse_program("1101,1,2,9,1001,9,3,9,99,0"));
+        machine.add_watchpoint(Watchpoint::new(9, 10, WatchKind::Write));
+        let hit = |access| {
+            ProgramResult::Watchpoint(WatchHit {
+                pc: 0,
+                instruction: 1101,
+                address: 9,
+                access,
+            })
+        };
+        assert_eq!(
+            machine.run(RunMode::ToHalt),
+            Ok(hit(Access::Write { old: 0, new: 3 }))
+        );
+        assert_eq!(machine.program_counter(), 4);
+        assert!(machine.remove_watchpoint(Watchpoint::new(9, 10, WatchKind::Write)));
+
+        machine.add_watchpoint(Watchpoint::new(0, 100, WatchKind::Read));
+        let result = machine.run(RunMode::ToHalt).unwrap();
+        assert_eq!(
+            result,
+            ProgramResult::Watchpoint(WatchHit {
+                pc: 4,
+                instruction: 1001,
+                address: 9,
+                access: Access::Read { value: 3 },
+            })
+        );
+        machine.watchpoints.clear();
+        assert_eq!(machine.run(RunMode::ToHalt), Ok(ProgramResult::Finished));
+        assert_eq!(machine.memory()[9], 6);
+    }
+
+    #[test]
+    fn test_run_with_devices() {
+        use super::device::FnInput;
+
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let mut inputs = vec![7, 8].into_iter();
+        let mut machine = Machine::with_devices(
+            parse_program("3,0,3,1,1,0,1,0,4,0,99"),
+            FnInput(|| inputs.next()),
+            sender,
+        );
+        machine.run(RunMode::ToHalt).unwrap();
+        assert!(machine.is_halted());
+        assert_eq!(receiver.recv(), Ok(15));
+    }
+}
@@ -0,0 +1,809 @@
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::error::Error;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::sync::{Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::fast::CachedMachine;
+use super::{IntcodeError, Machine, OperationResult};
+
+/// How many instructions a machine thread runs between checks for a stop
+/// request.
+const STOP_CHECK_INTERVAL: u32 = 10_000;
+
+/// Either end of a wire.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Port {
+    /// Output or input of the machine at this index.
+    Machine(usize),
+    /// A numbered input or output of the network as a whole.
+    External(usize),
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Wire {
+    pub from: Port,
+    pub to: Port,
+}
+
+/// Why `Network::run` stopped.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum NetworkState {
+    /// Every machine has halted.
+    Halted,
+    /// Nothing can run until more external input arrives.
+    Waiting,
+}
+
+/// A machine in the network failed.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct NetworkError {
+    pub machine: usize,
+    pub error: IntcodeError,
+}
+
+impl fmt::Display for NetworkError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "machine {}: {}", self.machine, self.error)
+    }
+}
+
+impl Error for NetworkError {}
+
+/// Why `Network::run_threaded` gave up.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum ThreadedError {
+    Machine(NetworkError),
+    /// Every machine that hasn't halted is blocked on input and no values
+    /// are in flight.
+    Deadlock {
+        waiting: Vec<usize>,
+    },
+    /// The time ran out while these machines were still running.
+    Timeout {
+        running: Vec<usize>,
+    },
+}
+
+impl fmt::Display for ThreadedError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ThreadedError::Machine(error) => write!(f, "{}", error),
+            ThreadedError::Deadlock { waiting } => {
+                write!(f, "deadlock, machines {:?} wait for input", waiting)
+            }
+            ThreadedError::Timeout { running } => {
+                write!(f, "timed out, machines {:?} still running", running)
+            }
+        }
+    }
+}
+
+impl Error for ThreadedError {}
+
+/// What a network needs from a machine to run it: `Machine`,
+/// `CachedMachine` and the structs `intcode compile` generates.
+pub trait Node {
+    /// Executes at least one instruction, stopping early at output, missing
+    /// input or a halt, and reports like `Machine::perform_operation`. Never
+    /// runs for long, so the caller can look at other work in between.
+    fn advance(&mut self) -> Result<OperationResult, IntcodeError>;
+
+    fn push_input(&mut self, value: i64);
+
+    /// Everything printed since the last call.
+    fn take_output(&mut self) -> Vec<i64>;
+
+    fn is_halted(&self) -> bool;
+
+    /// Number of instructions executed so far.
+    fn steps(&self) -> u64;
+}
+
+impl Node for Machine {
+    fn advance(&mut self) -> Result<OperationResult, IntcodeError> {
+        self.perform_operation()
+    }
+
+    fn push_input(&mut self, value: i64) {
+        Machine::push_input(self, value);
+    }
+
+    fn take_output(&mut self) -> Vec<i64> {
+        std::mem::take(self.output_mut())
+    }
+
+    fn is_halted(&self) -> bool {
+        Machine::is_halted(self)
+    }
+
+    fn steps(&self) -> u64 {
+        Machine::steps(self)
+    }
+}
+
+impl Node for CachedMachine {
+    fn advance(&mut self) -> Result<OperationResult, IntcodeError> {
+        self.perform_operation()
+    }
+
+    fn push_input(&mut self, value: i64) {
+        CachedMachine::push_input(self, value);
+    }
+
+    fn take_output(&mut self) -> Vec<i64> {
+        std::mem::take(self.output_mut())
+    }
+
+    fn is_halted(&self) -> bool {
+        CachedMachine::is_halted(self)
+    }
+
+    fn steps(&self) -> u64 {
+        CachedMachine::steps(self)
+    }
+}
+
+/// Machines connected by directed wires. A value printed by a machine is
+/// copied onto every wire leaving it. A machine with several incoming wires
+/// takes one value from each in turn, in the order the wires were connected,
+/// and waits for the wire whose turn it is even if others have values. That
+/// way neither scheduler's timing can change the result.
+pub struct Network<N = Machine> {
+    machines: Vec<N>,
+    wires: Vec<Wire>,
+    /// One queue per wire: values in flight, or everything printed to an
+    /// external output.
+    queues: Vec<VecDeque<i64>>,
+    /// Per machine, the incoming wire to take the next value from.
+    cursors: Vec<usize>,
+}
+
+impl<N: Node> Network<N> {
+    pub fn new(machines: Vec<N>) -> Network<N> {
+        Network {
+            cursors: vec![0; machines.len()],
+            machines,
+            wires: vec![],
+            queues: vec![],
+        }
+    }
+
+    /// Amplifiers in a row: external input 0 feeds the first, the last one
+    /// prints to external output 0. Panics if `machines` is empty.
+    pub fn series(machines: Vec<N>) -> Network<N> {
+        assert!(!machines.is_empty(), "a series needs at least one machine");
+        let last = machines.len() - 1;
+        let mut network = Network::new(machines);
+        network.connect(Port::External(0), Port::Machine(0));
+        for i in 0..last {
+            network.connect(Port::Machine(i), Port::Machine(i + 1));
+        }
+        network.connect(Port::Machine(last), Port::External(0));
+        network
+    }
+
+    /// Amplifiers in a ring, the last one also printing to external output 0.
+    /// There is no external input, since the first amplifier would have to
+    /// alternate between it and the ring; instead the ring starts out with
+    /// `signal` on its way to the first amplifier. Panics if `machines` is
+    /// empty.
+    pub fn feedback(machines: Vec<N>, signal: i64) -> Network<N> {
+        assert!(
+            !machines.is_empty(),
+            "a feedback loop needs at least one machine"
+        );
+        let last = machines.len() - 1;
+        let mut network = Network::new(machines);
+        network.connect(Port::Machine(last), Port::Machine(0));
+        network.queues[0].push_back(signal);
+        for i in 0..last {
+            network.connect(Port::Machine(i), Port::Machine(i + 1));
+        }
+        network.connect(Port::Machine(last), Port::External(0));
+        network
+    }
+
+    pub fn connect(&mut self, from: Port, to: Port) {
+        for &port in &[from, to] {
+            if let Port::Machine(index) = port {
+                assert!(index < self.machines.len(), "no machine {}", index);
+            }
+        }
+        self.wires.push(Wire { from, to });
+        self.queues.push(VecDeque::new());
+    }
+
+    #[cfg(test)]
+    pub fn machine(&self, index: usize) -> &N {
+        &self.machines[index]
+    }
+
+    pub fn push_input(&mut self, port: usize, value: i64) {
+        self.send(Port::External(port), value);
+    }
+
+    /// Everything printed to an external output so far. Values from several
+    /// wires are listed wire by wire.
+    pub fn output(&self, port: usize) -> Vec<i64> {
+        self.wires
+            .iter()
+            .zip(&self.queues)
+            .filter(|(wire, _)| wire.to == Port::External(port))
+            .flat_map(|(_, queue)| queue.iter().copied())
+            .collect()
+    }
+
+    /// Machines that haven't halted.
+    #[cfg(test)]
+    pub fn waiting(&self) -> Vec<usize> {
+        (0..self.machines.len())
+            .filter(|&index| !self.machines[index].is_halted())
+            .collect()
+    }
+
+    /// Runs machines in index order, each until it halts or waits for input,
+    /// and repeats until a whole round makes no progress.
+    pub fn run(&mut self) -> Result<NetworkState, NetworkError> {
+        loop {
+            let mut progress = false;
+            for index in 0..self.machines.len() {
+                self.deliver(index);
+                let machine = &mut self.machines[index];
+                let steps = machine.steps();
+                loop {
+                    let result = machine.advance().map_err(|error| NetworkError {
+                        machine: index,
+                        error,
+                    })?;
+                    match result {
+                        OperationResult::NeedsInput | OperationResult::Finished => break,
+                        _ => (),
+                    }
+                }
+                progress |= machine.steps() != steps;
+                for value in machine.take_output() {
+                    self.send(Port::Machine(index), value);
+                }
+            }
+            if !progress {
+                return Ok(if self.machines.iter().all(N::is_halted) {
+                    NetworkState::Halted
+                } else {
+                    NetworkState::Waiting
+                });
+            }
+        }
+    }
+
+    fn send(&mut self, from: Port, value: i64) {
+        for (wire, queue) in self.wires.iter().zip(&mut self.queues) {
+            if wire.from == from {
+                queue.push_back(value);
+            }
+        }
+    }
+
+    fn incoming(&self, index: usize) -> Vec<usize> {
+        (0..self.wires.len())
+            .filter(|&wire| self.wires[wire].to == Port::Machine(index))
+            .collect()
+    }
+
+    /// Moves values from a machine's incoming wires to its input, in turn,
+    /// until the wire whose turn it is runs dry.
+    fn deliver(&mut self, index: usize) {
+        let incoming = self.incoming(index);
+        if incoming.is_empty() {
+            return;
+        }
+        while let Some(value) = self.queues[incoming[self.cursors[index]]].pop_front() {
+            self.machines[index].push_input(value);
+            self.cursors[index] = (self.cursors[index] + 1) % incoming.len();
+        }
+    }
+
+    /// Like `run`, with every machine on its own thread and wires turned into
+    /// channels. Where `run` would return `Waiting` this reports a deadlock,
+    /// and it stops all machines once `timeout` has passed. Either way the
+    /// network keeps the state the machines stopped in.
+    pub fn run_threaded(&mut self, timeout: Duration) -> Result<NetworkState, ThreadedError>
+    where
+        N: Send,
+    {
+        let monitor = Monitor::new(self.wires.len());
+        let stop = AtomicBool::new(false);
+        let mut senders = vec![];
+        let mut receivers = vec![];
+        for (wire, queue) in self.wires.iter().zip(&mut self.queues) {
+            let (sender, receiver) = mpsc::channel();
+            if let Port::Machine(_) = wire.to {
+                monitor.update(|activity| activity.in_flight[senders.len()] = queue.len());
+                for value in queue.drain(..) {
+                    let _ = sender.send(Some(value));
+                }
+            }
+            senders.push(sender);
+            receivers.push(Some(receiver));
+        }
+
+        let machines: Vec<N> = self.machines.drain(..).collect();
+        let (outcome, stopped) = thread::scope(|scope| {
+            let mut threads = vec![];
+            for (index, mut machine) in machines.into_iter().enumerate() {
+                let mut inbox = Inbox {
+                    index,
+                    wires: self
+                        .incoming(index)
+                        .into_iter()
+                        .map(|wire| (wire, receivers[wire].take().unwrap()))
+                        .collect(),
+                    cursor: self.cursors[index],
+                    monitor: &monitor,
+                };
+                let mut outbox = Outbox {
+                    wires: (0..self.wires.len())
+                        .filter(|&wire| self.wires[wire].from == Port::Machine(index))
+                        .map(|wire| match self.wires[wire].to {
+                            Port::Machine(_) => (wire, Target::Machine(senders[wire].clone())),
+                            Port::External(_) => (wire, Target::External(vec![])),
+                        })
+                        .collect(),
+                    monitor: &monitor,
+                };
+                let (monitor, stop) = (&monitor, &stop);
+                threads.push(scope.spawn(move || {
+                    let result = run_until_stopped(&mut machine, &mut inbox, &mut outbox, stop);
+                    if machine.is_halted() || result.is_err() {
+                        monitor.update(|activity| {
+                            activity.done.insert(index);
+                            activity.failed |= result.is_err();
+                        });
+                    }
+                    (machine, inbox, outbox, result)
+                }));
+            }
+
+            let outcome = monitor.wait(threads.len(), Instant::now() + timeout);
+            if outcome != Some(Ok(NetworkState::Halted)) {
+                stop.store(true, Ordering::Relaxed);
+                for sender in &senders {
+                    let _ = sender.send(None);
+                }
+            }
+            let stopped: Vec<_> = threads
+                .into_iter()
+                .map(|thread| thread.join().expect("machine thread panicked"))
+                .collect();
+            (outcome, stopped)
+        });
+
+        let mut error = None;
+        for (index, (machine, inbox, outbox, result)) in stopped.into_iter().enumerate() {
+            if let (Err(e), None) = (result, error) {
+                error = Some(NetworkError {
+                    machine: index,
+                    error: e,
+                });
+            }
+            for (wire, receiver) in inbox.wires {
+                self.queues[wire].extend(receiver.try_iter().flatten());
+            }
+            for (wire, target) in outbox.wires {
+                if let Target::External(values) = target {
+                    self.queues[wire].extend(values);
+                }
+            }
+            self.cursors[index] = inbox.cursor;
+            self.machines.push(machine);
+        }
+        match error {
+            Some(error) => Err(ThreadedError::Machine(error)),
+            None => outcome.expect("a machine failed"),
+        }
+    }
+}
+
+/// What the machine threads are doing, for spotting deadlocks.
+struct Activity {
+    /// Per wire, values sent but not yet read.
+    in_flight: Vec<usize>,
+    /// Machines blocked on input, with the wire they wait on if they have
+    /// one.
+    waiting: BTreeMap<usize, Option<usize>>,
+    /// Machines that halted or failed.
+    done: BTreeSet<usize>,
+    failed: bool,
+}
+
+struct Monitor {
+    activity: Mutex<Activity>,
+    changed: Condvar,
+}
+
+impl Monitor {
+    fn new(wires: usize) -> Monitor {
+        Monitor {
+            activity: Mutex::new(Activity {
+                in_flight: vec![0; wires],
+                waiting: BTreeMap::new(),
+                done: BTreeSet::new(),
+                failed: false,
+            }),
+            changed: Condvar::new(),
+        }
+    }
+
+    fn update(&self, f: impl FnOnce(&mut Activity)) {
+        f(&mut self.activity.lock().unwrap());
+        self.changed.notify_all();
+    }
+
+    /// Waits until all `machines` are done, they are deadlocked or `deadline`
+    /// passes. `None` means a machine failed; the caller finds out which.
+    fn wait(
+        &self,
+        machines: usize,
+        deadline: Instant,
+    ) -> Option<Result<NetworkState, ThreadedError>> {
+        let mut activity = self.activity.lock().unwrap();
+        loop {
+            if activity.failed {
+                return None;
+            }
+            if activity.done.len() == machines {
+                return Some(Ok(NetworkState::Halted));
+            }
+            let stuck = activity
+                .waiting
+                .values()
+                .all(|wire| wire.is_none_or(|wire| activity.in_flight[wire] == 0));
+            if stuck && activity.done.len() + activity.waiting.len() == machines {
+                return Some(Err(ThreadedError::Deadlock {
+                    waiting: activity.waiting.keys().copied().collect(),
+                }));
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                let running = (0..machines)
+                    .filter(|index| {
+                        !activity.waiting.contains_key(index) && !activity.done.contains(index)
+                    })
+                    .collect();
+                return Some(Err(ThreadedError::Timeout { running }));
+            }
+            activity = self
+                .changed
+                .wait_timeout(activity, deadline - now)
+                .unwrap()
+                .0;
+        }
+    }
+}
+
+/// Input of a machine thread. `None` on a channel asks the machine to stop.
+struct Inbox<'a> {
+    index: usize,
+    wires: Vec<(usize, Receiver<Option<i64>>)>,
+    cursor: usize,
+    monitor: &'a Monitor,
+}
+
+impl Inbox<'_> {
+    /// The next value for the machine, blocking until it arrives. `None`
+    /// means the machine should stop.
+    fn receive(&mut self) -> Option<i64> {
+        let index = self.index;
+        let (wire, receiver) = match self.wires.get(self.cursor) {
+            Some(&(wire, ref receiver)) => (wire, receiver),
+            None => {
+                // nothing can ever arrive
+                self.monitor.update(|activity| {
+                    activity.waiting.insert(index, None);
+                });
+                return None;
+            }
+        };
+        let message = match receiver.try_recv() {
+            Ok(message) => message,
+            Err(TryRecvError::Disconnected) => None,
+            Err(TryRecvError::Empty) => {
+                self.monitor.update(|activity| {
+                    activity.waiting.insert(index, Some(wire));
+                });
+                receiver.recv().unwrap_or(None)
+            }
+        };
+        let value = message?;
+        self.monitor.update(|activity| {
+            activity.waiting.remove(&index);
+            activity.in_flight[wire] -= 1;
+        });
+        self.cursor = (self.cursor + 1) % self.wires.len();
+        Some(value)
+    }
+}
+
+enum Target {
+    Machine(Sender<Option<i64>>),
+    External(Vec<i64>),
+}
+
+/// Output of a machine thread, one target per outgoing wire.
+struct Outbox<'a> {
+    wires: Vec<(usize, Target)>,
+    monitor: &'a Monitor,
+}
+
+impl Outbox<'_> {
+    fn send(&mut self, value: i64) {
+        for (wire, target) in &mut self.wires {
+            match target {
+                Target::Machine(sender) => {
+                    // counted before sending so the value is never unaccounted for
+                    self.monitor
+                        .update(|activity| activity.in_flight[*wire] += 1);
+                    let _ = sender.send(Some(value));
+                }
+                Target::External(values) => values.push(value),
+            }
+        }
+    }
+}
+
+/// Runs until the machine halts, gets no more input or is asked to stop.
+fn run_until_stopped(
+    machine: &mut impl Node,
+    inbox: &mut Inbox,
+    outbox: &mut Outbox,
+    stop: &AtomicBool,
+) -> Result<(), IntcodeError> {
+    loop {
+        for _ in 0..STOP_CHECK_INTERVAL {
+            match machine.advance()? {
+                OperationResult::Output(_) => {
+                    for value in machine.take_output() {
+                        outbox.send(value);
+                    }
+                }
+                OperationResult::NeedsInput => match inbox.receive() {
+                    Some(value) => machine.push_input(value),
+                    None => return Ok(()),
+                },
+                OperationResult::Finished => return Ok(()),
+                _ => (),
+            }
+        }
+        if stop.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::fixtures::feedback_amplifier::FeedbackAmplifier;
+    use crate::intcode::fixtures::spinner::Spinner;
+    use crate::intcode::limit::Limits;
+    use crate::intcode::parse_program;
+
+    fn amplifiers(program: &str, phases: &[i64]) -> Vec<Machine> {
+        phases
+            .iter()
+            .map(|&phase| {
+                let mut machine = Machine::new(parse_program(program));
+                machine.push_input(phase);
+                machine
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_series() {
+        let program = "3,15,3,16,1002,16,10,16,1,16,15,15,4,15,99,0,0";
+        let mut network = Network::series(amplifiers(program, &[4, 3, 2, 1, 0]));
+        assert_eq!(network.run(), Ok(NetworkState::Waiting));
+        network.push_input(0, 0);
+        assert_eq!(network.run(), Ok(NetworkState::Halted));
+        assert_eq!(network.output(0), &[43210]);
+    }
+
+    #[test]
+    fn test_feedback() {
+        let program = "3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,27,\
+                       4,27,1001,28,-1,28,1005,28,6,99,0,0,5";
+        let mut network = Network::feedback(amplifiers(program, &[9, 8, 7, 6, 5]), 0);
+        assert_eq!(network.run(), Ok(NetworkState::Halted));
+        assert_eq!(network.output(0).last(), Some(&139_629_729));
+    }
+
+    #[test]
+    #[should_panic(expected = "a series needs at least one machine")]
+    fn test_empty_series() {
+        Network::<Machine>::series(vec![]);
+    }
+
+    #[test]
+    #[should_panic(expected = "a feedback loop needs at least one machine")]
+    fn test_empty_feedback() {
+        Network::<Machine>::feedback(vec![], 0);
+    }
+
+    #[test]
+    fn test_fan_out_and_fan_in() {
+        // doubles its input forever, and adds pairs of inputs forever
+        let double = "3,11,1002,11,2,11,4,11,1105,1,0,0";
+        let add = "3,13,3,14,1,13,14,15,4,15,1105,1,0,0,0,0";
+        let mut network = Network::new(vec![
+            Machine::new(parse_program(double)),
+            Machine::new(parse_program(double)),
+            Machine::new(parse_program(add)),
+        ]);
+        network.connect(Port::External(0), Port::Machine(0));
+        network.connect(Port::External(0), Port::Machine(1));
+        network.connect(Port::External(1), Port::Machine(2));
+        network.connect(Port::Machine(0), Port::Machine(2));
+        network.connect(Port::Machine(1), Port::Machine(2));
+        network.connect(Port::Machine(2), Port::External(0));
+        network.connect(Port::Machine(0), Port::External(1));
+
+        network.push_input(0, 5);
+        network.push_input(1, 100);
+        assert_eq!(network.run(), Ok(NetworkState::Waiting));
+        // the adder takes 100 from external input 1 and 10 from machine 0,
+        // then 10 from machine 1 with nothing left to pair it with
+        assert_eq!(network.output(0), &[110]);
+        assert_eq!(network.output(1), &[10]);
+        network.push_input(1, 1);
+        assert_eq!(network.run(), Ok(NetworkState::Waiting));
+        assert_eq!(network.output(0), &[110, 11]);
+    }
+
+    #[test]
+    fn test_error_names_machine() {
+        let mut network = Network::new(vec![
+            Machine::new(parse_program("99")),
+            Machine::new(parse_program("42")),
+        ]);
+        let error = network.run().unwrap_err();
+        assert_eq!(error.machine, 1);
+        assert_eq!(error.to_string(), "machine 1: invalid opcode 42 at pc 0");
+    }
+
+    const TIMEOUT: Duration = Duration::from_secs(10);
+
+    #[test]
+    fn test_threaded_matches_cooperative() {
+        let program = "3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,27,\
+                       4,27,1001,28,-1,28,1005,28,6,99,0,0,5";
+        let mut threaded = Network::feedback(amplifiers(program, &[9, 7, 8, 5, 6]), 0);
+        assert_eq!(threaded.run_threaded(TIMEOUT), Ok(NetworkState::Halted));
+        let mut cooperative = Network::feedback(amplifiers(program, &[9, 7, 8, 5, 6]), 0);
+        assert_eq!(cooperative.run(), Ok(NetworkState::Halted));
+        assert_eq!(threaded.output(0), cooperative.output(0));
+    }
+
+    #[test]
+    fn test_other_nodes() {
+        let program = "3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,27,\
+                       4,27,1001,28,-1,28,1005,28,6,99,0,0,5";
+        let cached = |phases: &[i64]| -> Vec<CachedMachine> {
+            phases
+                .iter()
+                .map(|&phase| {
+                    let mut machine = CachedMachine::new(parse_program(program));
+                    machine.push_input(phase);
+                    machine
+                })
+                .collect()
+        };
+        let compiled = |phases: &[i64]| -> Vec<FeedbackAmplifier> {
+            phases
+                .iter()
+                .map(|&phase| {
+                    let mut amplifier = FeedbackAmplifier::new();
+                    amplifier.push_input(phase);
+                    amplifier
+                })
+                .collect()
+        };
+        let phases = [9, 8, 7, 6, 5];
+        let mut network = Network::feedback(cached(&phases), 0);
+        assert_eq!(network.run(), Ok(NetworkState::Halted));
+        assert_eq!(network.output(0).last(), Some(&139_629_729));
+        let mut network = Network::feedback(compiled(&phases), 0);
+        assert_eq!(network.run(), Ok(NetworkState::Halted));
+        assert_eq!(network.output(0).last(), Some(&139_629_729));
+        assert!(network.machine(0).is_native());
+        let mut network = Network::feedback(compiled(&phases), 0);
+        assert_eq!(network.run_threaded(TIMEOUT), Ok(NetworkState::Halted));
+        assert_eq!(network.output(0).last(), Some(&139_629_729));
+
+        // native loops still notice when the threads are stopped
+        let mut network = Network::new(vec![Spinner::new(), Spinner::new()]);
+        assert_eq!(
+            network.run_threaded(Duration::from_millis(50)),
+            Err(ThreadedError::Timeout {
+                running: vec![0, 1]
+            })
+        );
+    }
+
+    #[test]
+    fn test_threaded_deadlock() {
+        // machine 1 waits for a second value machine 0 never sends
+        let mut network = Network::new(vec![
+            Machine::new(parse_program("104,1,99")),
+            Machine::new(parse_program("3,0,3,0,99")),
+            Machine::new(parse_program("3,0,99")),
+        ]);
+        network.connect(Port::Machine(0), Port::Machine(1));
+        assert_eq!(
+            network.run_threaded(TIMEOUT),
+            Err(ThreadedError::Deadlock {
+                waiting: vec![1, 2]
+            })
+        );
+        assert_eq!(network.waiting(), vec![1, 2]);
+        assert_eq!(network.machine(1).program_counter(), 2);
+
+        // the network can carry on from where the threads stopped
+        network.connect(Port::External(0), Port::Machine(2));
+        network.push_input(0, 5);
+        assert_eq!(network.run(), Ok(NetworkState::Waiting));
+        assert_eq!(network.waiting(), vec![1]);
+    }
+
+    #[test]
+    fn test_limits_apply_on_threads() {
+        let limits = Limits {
+            steps: Some(100),
+            time: None,
+        };
+        let mut spinning = Machine::new(parse_program("3,0,1105,1,2"));
+        spinning.set_limits(limits);
+        let mut network = Network::series(vec![spinning, Machine::new(parse_program("3,0,99"))]);
+        network.push_input(0, 1);
+        match network.run_threaded(TIMEOUT) {
+            Err(ThreadedError::Machine(NetworkError {
+                machine: 0,
+                error: IntcodeError::LimitExceeded { pc: 2, .. },
+            })) => {}
+            other => panic!("unexpected {:?}", other),
+        }
+        assert_eq!(network.machine(0).limits(), limits);
+    }
+
+    #[test]
+    fn test_threaded_timeout_and_errors() {
+        let mut network = Network::new(vec![
+            Machine::new(parse_program("1105,1,0")),
+            Machine::new(parse_program("3,0,99")),
+        ]);
+        assert_eq!(
+            network.run_threaded(Duration::from_millis(50)),
+            Err(ThreadedError::Timeout { running: vec![0] })
+        );
+
+        // halted machines aren't running either
+        let mut network = Network::new(vec![
+            Machine::new(parse_program("1105,1,0")),
+            Machine::new(parse_program("99")),
+        ]);
+        assert_eq!(
+            network.run_threaded(Duration::from_millis(50)),
+            Err(ThreadedError::Timeout { running: vec![0] })
+        );
+
+        let mut network = Network::new(vec![
+            Machine::new(parse_program("3,0,99")),
+            Machine::new(parse_program("42")),
+        ]);
+        let error = network.run_threaded(TIMEOUT).unwrap_err();
+        assert_eq!(error.to_string(), "machine 1: invalid opcode 42 at pc 0");
+    }
+}
@@ -0,0 +1,245 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use super::parse_opcode;
+use super::trace::{Step, Tracer};
+use super::AddressingMode;
+
+/// How many entries each section of the report lists.
+const TOP: usize = 10;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static PROFILE: Mutex<Option<Profile>> = Mutex::new(None);
+
+/// Execution counts gathered over one or more runs.
+#[derive(Clone, Default, Debug)]
+pub struct Profile {
+    total: u64,
+    /// Keyed by opcode, the instruction modulo 100.
+    opcodes: HashMap<i64, u64>,
+    /// Keyed by the whole instruction, opcode and modes.
+    instructions: HashMap<i64, u64>,
+    addresses: HashMap<usize, u64>,
+    /// Taken backward jumps, keyed by (target, pc of the jump).
+    loops: HashMap<(usize, usize), u64>,
+}
+
+impl Profile {
+    pub fn record(&mut self, pc: usize, instruction: i64, next_pc: usize) {
+        self.total += 1;
+        *self.opcodes.entry(instruction % 100).or_default() += 1;
+        *self.instructions.entry(instruction).or_default() += 1;
+        *self.addresses.entry(pc).or_default() += 1;
+        if next_pc <= pc && matches!(instruction % 100, 5 | 6) {
+            *self.loops.entry((next_pc, pc)).or_default() += 1;
+        }
+    }
+
+    pub fn merge(&mut self, other: &Profile) {
+        self.total += other.total;
+        for (&opcode, count) in &other.opcodes {
+            *self.opcodes.entry(opcode).or_default() += count;
+        }
+        for (&instruction, count) in &other.instructions {
+            *self.instructions.entry(instruction).or_default() += count;
+        }
+        for (&address, count) in &other.addresses {
+            *self.addresses.entry(address).or_default() += count;
+        }
+        for (&jump, count) in &other.loops {
+            *self.loops.entry(jump).or_default() += count;
+        }
+    }
+
+    /// Loops as (first address, jump address, iterations, instructions
+    /// executed inside), the busiest first.
+    pub fn hot_loops(&self) -> Vec<(usize, usize, u64, u64)> {
+        let mut loops: Vec<_> = self
+            .loops
+            .iter()
+            .map(|(&(start, end), &iterations)| {
+                let inside: u64 = (start..=end)
+                    .filter_map(|address| self.addresses.get(&address))
+                    .sum();
+                (start, end, iterations, inside)
+            })
+            .collect();
+        loops.sort_by(|a, b| b.3.cmp(&a.3).then(a.0.cmp(&b.0)));
+        loops
+    }
+}
+
+/// Sorts counts descending, ties broken by key.
+fn top<K: Copy + Ord>(counts: &HashMap<K, u64>) -> Vec<(K, u64)> {
+    let mut counts: Vec<(K, u64)> = counts.iter().map(|(&key, &count)| (key, count)).collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    counts.truncate(TOP);
+    counts
+}
+
+fn mnemonic(instruction: i64) -> &'static str {
+    parse_opcode(instruction).map_or("???", |operation| operation.mnemonic())
+}
+
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "instructions executed: {}", self.total)?;
+        let share = |count: u64| 100.0 * count as f64 / self.total.max(1) as f64;
+
+        writeln!(f, "by opcode:")?;
+        for (opcode, count) in top(&self.opcodes) {
+            let name = mnemonic(opcode);
+            writeln!(f, "  {:<4} {:>12} {:>5.1}%", name, count, share(count))?;
+        }
+
+        writeln!(f, "by addressing modes:")?;
+        for (instruction, count) in top(&self.instructions) {
+            let modes: Vec<&str> = parse_opcode(instruction)
+                .map(|operation| operation.parameter_modes())
+                .unwrap_or_default()
+                .into_iter()
+                .map(|mode| match mode {
+                    AddressingMode::Indirect => "pos",
+                    AddressingMode::Immediate => "imm",
+                    AddressingMode::Relative => "rel",
+                })
+                .collect();
+            writeln!(
+                f,
+                "  {:<5} {:<4} {:<12} {:>12} {:>5.1}%",
+                instruction,
+                mnemonic(instruction),
+                modes.join(","),
+                count,
+                share(count)
+            )?;
+        }
+
+        writeln!(f, "hottest addresses:")?;
+        for (address, count) in top(&self.addresses) {
+            writeln!(f, "  {:04} {:>12} {:>5.1}%", address, count, share(count))?;
+        }
+
+        writeln!(f, "hot loops:")?;
+        for (start, end, iterations, inside) in self.hot_loops().into_iter().take(TOP) {
+            writeln!(
+                f,
+                "  {:04}..{:04} {:>12} iterations {:>12} instructions {:>5.1}%",
+                start,
+                end,
+                iterations,
+                inside,
+                share(inside)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Turns on profiling for every machine created from now on.
+pub fn enable() {
+    *PROFILE.lock().unwrap() = Some(Profile::default());
+    ENABLED.store(true, Ordering::SeqCst);
+}
+
+/// A profiler for a new machine, if profiling is enabled.
+pub fn profiler() -> Option<Profiler> {
+    if ENABLED.load(Ordering::Relaxed) {
+        Some(Profiler::default())
+    } else {
+        None
+    }
+}
+
+/// Everything recorded since `enable`, once all profilers are dropped.
+pub fn collected() -> Option<Profile> {
+    PROFILE.lock().unwrap().clone()
+}
+
+/// Counts for a single machine, added to the global profile when dropped.
+#[derive(Default)]
+pub struct Profiler {
+    profile: Profile,
+}
+
+/// A copy starts counting from zero so nothing is merged twice.
+impl Clone for Profiler {
+    fn clone(&self) -> Profiler {
+        Profiler::default()
+    }
+}
+
+impl Profiler {
+    pub fn record(&mut self, pc: usize, instruction: i64, next_pc: usize) {
+        self.profile.record(pc, instruction, next_pc);
+    }
+}
+
+impl<W> Tracer<W> for Profiler {
+    fn record(&mut self, step: &Step<W>) {
+        self.profile.record(step.pc, step.instruction, step.next_pc);
+    }
+}
+
+impl Drop for Profiler {
+    fn drop(&mut self) {
+        if let Ok(mut global) = PROFILE.lock() {
+            if let Some(global) = global.as_mut() {
+                global.merge(&self.profile);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profile_counts() {
+        let mut profiler = Profiler::default();
+        for (pc, instruction, next) in &[(0, 1001, 4), (4, 1005, 0), (4, 1005, 7), (7, 99, 7)] {
+            profiler.record(*pc, *instruction, *next);
+        }
+        let profile = &profiler.profile;
+        assert_eq!(profile.total, 4);
+        assert_eq!(profile.opcodes[&5], 2);
+        assert_eq!(profile.addresses[&4], 2);
+        assert_eq!(profile.hot_loops(), vec![(0, 4, 1, 3)]);
+
+        let mut merged = Profile::default();
+        merged.merge(profile);
+        merged.merge(profile);
+        assert_eq!(merged.total, 8);
+        assert_eq!(merged.instructions[&1001], 2);
+    }
+
+    #[test]
+    fn test_report() {
+        let mut profile = Profile::default();
+        for &(pc, instruction, next) in &[(0, 1001, 4), (4, 1005, 0), (0, 1001, 4), (4, 1005, 7)] {
+            profile.record(pc, instruction, next);
+        }
+        profile.record(7, 99, 7);
+        assert_eq!(
+            profile.to_string(),
+            "instructions executed: 5\n\
+             by opcode:\n  \
+             ADD             2  40.0%\n  \
+             JNZ             2  40.0%\n  \
+             HLT             1  20.0%\n\
+             by addressing modes:\n  \
+             1001  ADD  pos,imm,pos             2  40.0%\n  \
+             1005  JNZ  pos,imm                 2  40.0%\n  \
+             99    HLT                          1  20.0%\n\
+             hottest addresses:\n  \
+             0000            2  40.0%\n  \
+             0004            2  40.0%\n  \
+             0007            1  20.0%\n\
+             hot loops:\n  \
+             0000..0004            1 iterations            4 instructions  80.0%\n"
+        );
+    }
+}
@@ -0,0 +1,148 @@
+use std::collections::VecDeque;
+use std::io::{self, BufRead, Write};
+
+use super::Memory;
+
+const HEADER: &str = "intcode-snapshot 1";
+
+/// Complete state of a machine apart from its output device.
+#[derive(Clone, Debug)]
+pub struct Snapshot<I, W = i64> {
+    pub memory: Memory<W>,
+    pub program_counter: usize,
+    pub relative_base: i64,
+    pub halted: bool,
+    pub steps: u64,
+    pub input: I,
+}
+
+impl Snapshot<VecDeque<i64>> {
+    /// Writes the snapshot as text, listing every allocated memory page
+    /// without its trailing zeros.
+    pub fn write_to(&self, mut writer: impl Write) -> io::Result<()> {
+        writeln!(writer, "{}", HEADER)?;
+        writeln!(writer, "pc {}", self.program_counter)?;
+        writeln!(writer, "rb {}", self.relative_base)?;
+        writeln!(writer, "halted {}", self.halted)?;
+        writeln!(writer, "steps {}", self.steps)?;
+        writeln!(writer, "input {}", join(self.input.iter()))?;
+        for (start, page) in self.memory.pages() {
+            let used = page
+                .iter()
+                .rposition(|&value| value != 0)
+                .map_or(0, |i| i + 1);
+            if used > 0 {
+                writeln!(writer, "mem {} {}", start, join(page[..used].iter()))?;
+            }
+        }
+        writer.flush()
+    }
+
+    pub fn read_from(reader: impl BufRead) -> io::Result<Snapshot<VecDeque<i64>>> {
+        let invalid = |line: &str| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid snapshot line {}", line),
+            )
+        };
+        let mut lines = reader.lines();
+        if lines.next().transpose()?.as_deref() != Some(HEADER) {
+            return Err(invalid("header"));
+        }
+
+        let mut snapshot = Snapshot {
+            memory: Memory::new(),
+            program_counter: 0,
+            relative_base: 0,
+            halted: false,
+            steps: 0,
+            input: VecDeque::new(),
+        };
+        for line in lines {
+            let line = line?;
+            let (key, value) = line.split_once(' ').unwrap_or((&line, ""));
+            let parsed = match key {
+                "pc" => value.parse().map(|pc| snapshot.program_counter = pc).ok(),
+                "rb" => value.parse().map(|rb| snapshot.relative_base = rb).ok(),
+                "halted" => value.parse().map(|halted| snapshot.halted = halted).ok(),
+                "steps" => value.parse().map(|steps| snapshot.steps = steps).ok(),
+                "input" => split(value).map(|input| snapshot.input = input.into()),
+                "mem" => value.split_once(' ').and_then(|(start, values)| {
+                    let start: usize = start.parse().ok()?;
+                    for (offset, value) in split(values)?.into_iter().enumerate() {
+                        snapshot.memory.write(start + offset, value);
+                    }
+                    Some(())
+                }),
+                "" => Some(()),
+                _ => None,
+            };
+            parsed.ok_or_else(|| invalid(&line))?;
+        }
+        Ok(snapshot)
+    }
+}
+
+fn join<'a>(values: impl Iterator<Item = &'a i64>) -> String {
+    values
+        .map(|value| value.to_string())
+        .collect::<Vec<String>>()
+        .join(",")
+}
+
+fn split(values: &str) -> Option<Vec<i64>> {
+    values
+        .split(',')
+        .filter(|value| !value.is_empty())
+        .map(|value| value.parse().ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::{parse_program, Machine, ProgramResult, RunMode};
+
+    #[test]
+    fn test_snapshot_and_restore() {
+        let mut machine = Machine::new(parse_program("3,9,1001,9,1,9,4,9,99,0"));
+        machine.push_input(1);
+        let snapshot = machine.snapshot();
+        machine.run(RunMode::ToHalt).unwrap();
+        assert_eq!(machine.output(), &vec![2]);
+
+        machine.restore(&snapshot);
+        machine.run(RunMode::ToHalt).unwrap();
+        assert_eq!(machine.output(), &vec![2, 2]);
+    }
+
+    #[test]
+    fn test_snapshot_file_round_trip() {
+        let mut machine = Machine::new(parse_program("3,11,3,12,1,11,12,2000,4,2000,99,0,0"));
+        machine.push_input(40);
+        assert_eq!(
+            machine.run(RunMode::UntilInput),
+            Ok(ProgramResult::NeedsInput)
+        );
+        machine.push_input(2);
+        machine.push_input(7);
+
+        let mut file = vec![];
+        machine.snapshot().write_to(&mut file).unwrap();
+        let snapshot = Snapshot::read_from(&file[..]).unwrap();
+        assert_eq!(snapshot.program_counter, 2);
+        assert_eq!(snapshot.steps, 1);
+        assert_eq!(snapshot.input, vec![2, 7]);
+
+        let mut resumed = Machine::from_snapshot(snapshot, vec![]);
+        resumed.run(RunMode::ToHalt).unwrap();
+        assert_eq!(resumed.output(), &vec![42]);
+        assert_eq!(resumed.snapshot().input, vec![7]);
+    }
+
+    #[test]
+    fn test_read_invalid_snapshot() {
+        assert!(Snapshot::read_from("pc 1\n".as_bytes()).is_err());
+        assert!(Snapshot::read_from("intcode-snapshot 1\nmem x 1\n".as_bytes()).is_err());
+    }
+}
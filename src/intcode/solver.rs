@@ -0,0 +1,317 @@
+use std::collections::VecDeque;
+use std::ops::Range;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread;
+
+use super::fast::CachedMachine;
+use super::limit::Limits;
+use super::symbolic::{self, Formula, SymbolicError};
+use super::RunMode;
+
+/// Instructions a candidate may run for unless the search says otherwise.
+pub const DEFAULT_STEPS: u64 = 1_000_000;
+
+/// A memory cell to set before running, with the values to try.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Parameter {
+    pub address: usize,
+    pub values: Range<i64>,
+}
+
+/// Looks for parameter values that make a program reach a goal. Candidates
+/// are numbered with the first parameter varying slowest, like nested loops,
+/// and results always come in that order however many workers search.
+/// Programs that fail or run into `limits` are not solutions.
+#[derive(Clone, Debug)]
+pub struct Search {
+    pub program: Vec<i64>,
+    pub parameters: Vec<Parameter>,
+    pub input: Vec<i64>,
+    pub workers: usize,
+    /// Applied to every candidate separately.
+    pub limits: Limits,
+}
+
+impl Parameter {
+    /// Number of values to try, which needs 64 bits for a range spanning
+    /// all of `i64`.
+    fn len(&self) -> u64 {
+        let len = i128::from(self.values.end) - i128::from(self.values.start);
+        len.max(0) as u64
+    }
+}
+
+/// Goal met when the program leaves `value` at `address`.
+pub fn memory_equals(address: usize, value: i64) -> impl Fn(&CachedMachine) -> bool + Sync {
+    move |machine| machine.read(address) == value
+}
+
+/// Goal met when the program prints exactly `expected`.
+#[cfg(test)]
+pub fn output_equals(expected: Vec<i64>) -> impl Fn(&CachedMachine) -> bool + Sync {
+    move |machine| machine.output() == &expected
+}
+
+/// Parses a half-open range like `0..100`.
+pub fn parse_range(text: &str) -> Option<Range<i64>> {
+    let (start, end) = text.split_once("..")?;
+    Some(start.trim().parse().ok()?..end.trim().parse().ok()?)
+}
+
+impl Search {
+    pub fn new(program: Vec<i64>) -> Search {
+        Search {
+            program,
+            parameters: vec![],
+            input: vec![],
+            workers: 1,
+            limits: Limits {
+                steps: Some(DEFAULT_STEPS),
+                time: None,
+            },
+        }
+    }
+
+    pub fn parameter(&mut self, address: usize, values: Range<i64>) {
+        self.parameters.push(Parameter { address, values });
+    }
+
+    /// The first solution in candidate order.
+    pub fn first(&self, goal: impl Fn(&CachedMachine) -> bool + Sync) -> Option<Vec<i64>> {
+        self.search(&goal, true).into_iter().next()
+    }
+
+    /// Every solution, in candidate order.
+    pub fn all(&self, goal: impl Fn(&CachedMachine) -> bool + Sync) -> Vec<Vec<i64>> {
+        self.search(&goal, false)
+    }
+
+    /// The final value of `address` as a formula in the parameters.
+    pub fn formula(&self, address: usize) -> Result<Formula, SymbolicError> {
+        let parameters: Vec<usize> = self.parameters.iter().map(|p| p.address).collect();
+        symbolic::evaluate(&self.program, &parameters, address)
+    }
+
+    /// Solutions for the goal `memory_equals(address, value)`, the first one
+    /// only if `first` is set. Solves the formula for the cell if there is
+    /// one and runs only its solutions instead of every candidate.
+    pub fn memory_solutions(&self, address: usize, value: i64, first: bool) -> Vec<Vec<i64>> {
+        match self.formula(address) {
+            Ok(formula) => {
+                let ranges: Vec<Range<i64>> = self
+                    .parameters
+                    .iter()
+                    .map(|parameter| parameter.values.clone())
+                    .collect();
+                // the formula can't see intermediate results overflowing,
+                // which fails the program, so each solution is run once
+                let initial = self.initial();
+                let mut machine = initial.clone();
+                let goal = memory_equals(address, value);
+                let confirmed = formula
+                    .solve(value, &ranges)
+                    .into_iter()
+                    .filter(|values| self.solves(&mut machine, &initial, values, &goal));
+                if first {
+                    confirmed.take(1).collect()
+                } else {
+                    confirmed.collect()
+                }
+            }
+            Err(_) if first => self
+                .first(memory_equals(address, value))
+                .into_iter()
+                .collect(),
+            Err(_) => self.all(memory_equals(address, value)),
+        }
+    }
+
+    /// Number of candidates, saturating at `u64::MAX`.
+    fn candidates(&self) -> u64 {
+        self.parameters
+            .iter()
+            .map(Parameter::len)
+            .fold(1, u64::saturating_mul)
+    }
+
+    fn candidate(&self, mut index: u64) -> Vec<i64> {
+        let mut values = vec![0; self.parameters.len()];
+        for (value, parameter) in values.iter_mut().zip(&self.parameters).rev() {
+            let len = parameter.len();
+            *value = (i128::from(parameter.values.start) + i128::from(index % len)) as i64;
+            index /= len;
+        }
+        values
+    }
+
+    /// The machine every candidate starts from.
+    fn initial(&self) -> CachedMachine {
+        CachedMachine::with_devices(
+            self.program.clone(),
+            self.input.iter().copied().collect::<VecDeque<i64>>(),
+            vec![],
+        )
+    }
+
+    /// Runs the candidate `values` on `machine`, reset to `initial`.
+    fn solves(
+        &self,
+        machine: &mut CachedMachine,
+        initial: &CachedMachine,
+        values: &[i64],
+        goal: &(dyn Fn(&CachedMachine) -> bool + Sync),
+    ) -> bool {
+        machine.clone_from(initial);
+        for (parameter, &value) in self.parameters.iter().zip(values) {
+            machine.write(parameter.address, value);
+        }
+        machine.set_limits(self.limits);
+        machine.run(RunMode::ToHalt).is_ok() && goal(machine)
+    }
+
+    fn search(&self, goal: &(dyn Fn(&CachedMachine) -> bool + Sync), first: bool) -> Vec<Vec<i64>> {
+        let count = self.candidates();
+        let next = AtomicU64::new(0);
+        // once a solution is found in `first` mode, nothing after it matters
+        let limit = AtomicU64::new(count);
+        let initial = self.initial();
+
+        let mut found: Vec<(u64, Vec<i64>)> = thread::scope(|scope| {
+            let workers: Vec<_> = (0..self.workers.max(1))
+                .map(|_| {
+                    scope.spawn(|| {
+                        let mut found = vec![];
+                        let mut machine = initial.clone();
+                        loop {
+                            let index = next.fetch_add(1, Ordering::Relaxed);
+                            if index >= limit.load(Ordering::Relaxed) {
+                                return found;
+                            }
+                            let values = self.candidate(index);
+                            if self.solves(&mut machine, &initial, &values, goal) {
+                                found.push((index, values));
+                                if first {
+                                    limit.fetch_min(index, Ordering::Relaxed);
+                                }
+                            }
+                        }
+                    })
+                })
+                .collect();
+            workers
+                .into_iter()
+                .flat_map(|worker| worker.join().expect("solver thread panicked"))
+                .collect()
+        });
+        found.sort_unstable_by_key(|&(index, _)| index);
+        if first {
+            found.truncate(1);
+        }
+        found.into_iter().map(|(_, values)| values).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::parse_program;
+
+    fn adder() -> Search {
+        // cells 1 and 2 are immediate summands, the sum goes to 0
+        let mut search = Search::new(parse_program("1101,0,0,0,99"));
+        search.parameter(1, 0..5);
+        search.parameter(2, 0..5);
+        search
+    }
+
+    #[test]
+    fn test_candidate_order() {
+        let search = adder();
+        assert_eq!(search.candidates(), 25);
+        assert_eq!(search.candidate(0), vec![0, 0]);
+        assert_eq!(search.candidate(7), vec![1, 2]);
+        assert_eq!(search.candidate(24), vec![4, 4]);
+
+        let mut search = Search::new(vec![99]);
+        search.parameter(0, i64::MIN..i64::MAX);
+        search.parameter(1, -1..1);
+        assert_eq!(search.candidates(), u64::MAX);
+        assert_eq!(search.candidate(1), vec![i64::MIN, 0]);
+        assert_eq!(search.candidate(3), vec![i64::MIN + 1, 0]);
+        search.parameters.pop();
+        assert_eq!(search.candidate(u64::MAX - 1), vec![i64::MAX - 1]);
+    }
+
+    #[test]
+    fn test_first_and_all() {
+        let mut search = adder();
+        let expected = vec![vec![0, 4], vec![1, 3], vec![2, 2], vec![3, 1], vec![4, 0]];
+        for &workers in &[1, 4] {
+            search.workers = workers;
+            assert_eq!(search.all(memory_equals(0, 4)), expected);
+            assert_eq!(search.first(memory_equals(0, 4)), Some(vec![0, 4]));
+            assert_eq!(search.first(memory_equals(0, 8)), Some(vec![4, 4]));
+            assert_eq!(search.first(memory_equals(0, 10)), None);
+        }
+    }
+
+    #[test]
+    fn test_memory_solutions() {
+        let mut search = Search::new(parse_program(
+            "1,0,0,3,1,1,2,3,1002,3,5,3,2,3,1,0,1001,0,7,0,99",
+        ));
+        search.parameter(1, 0..30);
+        search.parameter(2, 0..30);
+        assert!(search.formula(0).is_ok());
+        let all = search.all(memory_equals(0, 112));
+        assert_eq!(all, vec![vec![1, 20], vec![3, 4]]);
+        assert_eq!(search.memory_solutions(0, 112, false), all);
+        assert_eq!(search.memory_solutions(0, 112, true), vec![vec![1, 20]]);
+
+        // jumps rule out a formula, so the search runs the program
+        let mut search = Search::new(parse_program("1105,1,4,0,1101,0,0,0,99"));
+        search.parameter(5, 0..10);
+        search.parameter(6, 0..10);
+        assert!(search.formula(0).is_err());
+        assert_eq!(
+            search.memory_solutions(0, 17, false),
+            vec![vec![8, 9], vec![9, 8]]
+        );
+
+        // `t - t + verb` with `t = noun * 10^18` has a formula, but the
+        // machine overflows computing `t` for nouns from 10 on
+        let mut search = Search::new(parse_program(
+            "1002,20,1000000000000000000,22,1002,22,-1,23,1,22,23,0,1,0,21,0,99,0,0,0,0,0,0,0",
+        ));
+        search.parameter(20, 0..20);
+        search.parameter(21, 0..20);
+        assert!(search.formula(0).is_ok());
+        let all = search.all(memory_equals(0, 5));
+        assert_eq!(all.len(), 10);
+        assert_eq!(search.memory_solutions(0, 5, false), all);
+        assert_eq!(search.memory_solutions(0, 5, true), vec![vec![0, 5]]);
+    }
+
+    #[test]
+    fn test_goals_and_failures() {
+        let mut search = Search::new(parse_program("3,0,102,2,0,0,4,0,99"));
+        search.input = vec![21];
+        search.parameter(3, 0..10);
+        search.workers = 3;
+        assert_eq!(search.all(output_equals(vec![42])), vec![vec![2]]);
+
+        // writing a bad opcode over the halt fails rather than matching
+        let mut search = Search::new(parse_program("1101,0,0,4,99"));
+        search.parameter(1, 0..100);
+        assert_eq!(search.first(memory_equals(4, 99)), Some(vec![99]));
+        assert_eq!(search.all(memory_equals(4, 42)), Vec::<Vec<i64>>::new());
+
+        // a candidate that loops forever runs into the step limit
+        let mut search = Search::new(parse_program("1105,0,7,1105,1,0,0,99"));
+        search.parameter(1, -1..2);
+        search.limits.steps = Some(100);
+        assert_eq!(search.all(|_| true), vec![vec![-1], vec![1]]);
+        assert_eq!(parse_range("-5..5"), Some(-5..5));
+        assert_eq!(parse_range("5"), None);
+    }
+}
@@ -0,0 +1,456 @@
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::ops::Range;
+
+use super::{parse_opcode, AddressingMode, Operation};
+
+/// Integer polynomial over the parameter cells. Terms are keyed by the
+/// exponent of each variable, in parameter order.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Polynomial {
+    terms: BTreeMap<Vec<u32>, i64>,
+    variables: usize,
+}
+
+impl Polynomial {
+    fn constant(value: i64, variables: usize) -> Polynomial {
+        let mut terms = BTreeMap::new();
+        if value != 0 {
+            terms.insert(vec![0; variables], value);
+        }
+        Polynomial { terms, variables }
+    }
+
+    fn variable(index: usize, variables: usize) -> Polynomial {
+        let mut exponents = vec![0; variables];
+        exponents[index] = 1;
+        let mut terms = BTreeMap::new();
+        terms.insert(exponents, 1);
+        Polynomial { terms, variables }
+    }
+
+    /// The value, if it doesn't depend on any variable.
+    pub fn as_constant(&self) -> Option<i64> {
+        match self.terms.iter().next() {
+            None => Some(0),
+            Some((exponents, &value))
+                if self.terms.len() == 1 && exponents.iter().all(|&e| e == 0) =>
+            {
+                Some(value)
+            }
+            Some(_) => None,
+        }
+    }
+
+    fn checked_add(&self, other: &Polynomial) -> Option<Polynomial> {
+        let mut sum = self.clone();
+        for (exponents, &value) in &other.terms {
+            let entry = sum.terms.entry(exponents.clone()).or_insert(0);
+            *entry = entry.checked_add(value)?;
+            if *entry == 0 {
+                sum.terms.remove(exponents);
+            }
+        }
+        Some(sum)
+    }
+
+    fn checked_mul(&self, other: &Polynomial) -> Option<Polynomial> {
+        let mut product = Polynomial::constant(0, self.variables);
+        for (left, &a) in &self.terms {
+            for (right, &b) in &other.terms {
+                let exponents = left.iter().zip(right).map(|(x, y)| x + y).collect();
+                let mut term = BTreeMap::new();
+                term.insert(exponents, a.checked_mul(b)?);
+                product = product.checked_add(&Polynomial {
+                    terms: term,
+                    variables: self.variables,
+                })?;
+            }
+        }
+        Some(product)
+    }
+
+    /// The value at `values`, or `None` on overflow.
+    pub fn evaluate(&self, values: &[i64]) -> Option<i64> {
+        self.terms
+            .iter()
+            .try_fold(0i64, |sum, (exponents, &coefficient)| {
+                let term = exponents
+                    .iter()
+                    .zip(values)
+                    .try_fold(coefficient, |term, (&exponent, &value)| {
+                        term.checked_mul(value.checked_pow(exponent)?)
+                    })?;
+                sum.checked_add(term)
+            })
+    }
+
+    /// Substitutes `values` for all variables but the last and returns the
+    /// coefficients of the remaining polynomial in the last variable, lowest
+    /// power first.
+    fn coefficients_in_last(&self, values: &[i64]) -> Option<Vec<i64>> {
+        let mut coefficients: Vec<i64> = vec![];
+        for (exponents, &coefficient) in &self.terms {
+            let (&power, rest) = exponents.split_last()?;
+            let term = rest
+                .iter()
+                .zip(values)
+                .try_fold(coefficient, |term, (&exponent, &value)| {
+                    term.checked_mul(value.checked_pow(exponent)?)
+                })?;
+            let power = power as usize;
+            if coefficients.len() <= power {
+                coefficients.resize(power + 1, 0);
+            }
+            coefficients[power] = coefficients[power].checked_add(term)?;
+        }
+        Some(coefficients)
+    }
+}
+
+/// Displays terms highest degree first, with variables named after their
+/// cells like `360000*[1] + [2] + 1690720`. `Formula` fills in the addresses.
+struct Named<'a> {
+    polynomial: &'a Polynomial,
+    addresses: &'a [usize],
+}
+
+impl fmt::Display for Named<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut terms: Vec<(&Vec<u32>, i64)> = self
+            .polynomial
+            .terms
+            .iter()
+            .map(|(exponents, &value)| (exponents, value))
+            .collect();
+        if terms.is_empty() {
+            return write!(f, "0");
+        }
+        terms.sort_by_key(|&(exponents, _)| {
+            let degree: u32 = exponents.iter().sum();
+            (Reverse(degree), Reverse(exponents.clone()))
+        });
+        for (i, (exponents, coefficient)) in terms.into_iter().enumerate() {
+            let factors: Vec<String> = exponents
+                .iter()
+                .zip(self.addresses)
+                .filter(|&(&exponent, _)| exponent > 0)
+                .map(|(&exponent, address)| match exponent {
+                    1 => format!("[{}]", address),
+                    _ => format!("[{}]^{}", address, exponent),
+                })
+                .collect();
+            let sign = if coefficient < 0 { "-" } else { "+" };
+            match (i, sign) {
+                (0, "-") => write!(f, "-")?,
+                (0, _) => (),
+                _ => write!(f, " {} ", sign)?,
+            }
+            let magnitude = coefficient.unsigned_abs();
+            if factors.is_empty() {
+                write!(f, "{}", magnitude)?;
+            } else if magnitude == 1 {
+                write!(f, "{}", factors.join("*"))?;
+            } else {
+                write!(f, "{}*{}", magnitude, factors.join("*"))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Why a program can't be turned into a formula.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum SymbolicError {
+    /// Only addition, multiplication and halt are supported.
+    Unsupported { pc: usize, instruction: i64 },
+    /// The instruction word depends on the parameters.
+    VariableInstruction { pc: usize },
+    /// A result is written to an address that depends on the parameters.
+    VariableWrite { pc: usize },
+    /// The cell asked for depends on memory read from a variable address.
+    DataDependent { address: usize },
+    /// Some coefficient doesn't fit an `i64`.
+    Overflow { pc: usize },
+}
+
+impl fmt::Display for SymbolicError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SymbolicError::Unsupported { pc, instruction } => {
+                write!(f, "unsupported instruction {} at pc {}", instruction, pc)
+            }
+            SymbolicError::VariableInstruction { pc } => {
+                write!(f, "instruction at pc {} depends on the parameters", pc)
+            }
+            SymbolicError::VariableWrite { pc } => {
+                write!(f, "write at pc {} goes to a variable address", pc)
+            }
+            SymbolicError::DataDependent { address } => {
+                write!(
+                    f,
+                    "[{}] depends on data read from a variable address",
+                    address
+                )
+            }
+            SymbolicError::Overflow { pc } => write!(f, "coefficients overflow at pc {}", pc),
+        }
+    }
+}
+
+impl Error for SymbolicError {}
+
+/// A memory cell's final value as a polynomial in the parameter cells.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Formula {
+    pub address: usize,
+    pub value: Polynomial,
+    /// Addresses of the parameter cells, the polynomial's variables.
+    pub parameters: Vec<usize>,
+    /// Variable addresses the program reads from, which have to be
+    /// non-negative for it to run at all.
+    pub reads: Vec<Polynomial>,
+}
+
+impl Formula {
+    fn runs(&self, values: &[i64]) -> bool {
+        self.reads
+            .iter()
+            .all(|read| read.evaluate(values).is_some_and(|address| address >= 0))
+    }
+
+    /// Parameter values in `ranges` for which the cell ends up as `target`,
+    /// the first parameter varying slowest. All but the last parameter are
+    /// enumerated; the last is solved for when the formula is linear in it.
+    pub fn solve(&self, target: i64, ranges: &[Range<i64>]) -> Vec<Vec<i64>> {
+        let (last, rest) = match ranges.split_last() {
+            Some(split) => split,
+            None => {
+                let matches = self.value.evaluate(&[]) == Some(target) && self.runs(&[]);
+                return if matches { vec![vec![]] } else { vec![] };
+            }
+        };
+        let mut solutions = vec![];
+        let mut prefix = vec![];
+        self.solve_prefix(target, rest, last, &mut prefix, &mut solutions);
+        solutions
+    }
+
+    fn solve_prefix(
+        &self,
+        target: i64,
+        rest: &[Range<i64>],
+        last: &Range<i64>,
+        prefix: &mut Vec<i64>,
+        solutions: &mut Vec<Vec<i64>>,
+    ) {
+        if let Some((range, rest)) = rest.split_first() {
+            for value in range.clone() {
+                prefix.push(value);
+                self.solve_prefix(target, rest, last, prefix, solutions);
+                prefix.pop();
+            }
+            return;
+        }
+        let mut accept = |value: i64| {
+            let mut values = prefix.clone();
+            values.push(value);
+            if self.value.evaluate(&values) == Some(target) && self.runs(&values) {
+                solutions.push(values);
+            }
+        };
+        let mut coefficients = self.value.coefficients_in_last(prefix);
+        if let Some(coefficients) = coefficients.as_mut() {
+            while coefficients.last() == Some(&0) {
+                coefficients.pop();
+            }
+        }
+        match coefficients.as_deref() {
+            Some(&[b, a]) => {
+                // a * x + b == target, where an overflowing x is out of range
+                let x = target.checked_sub(b).and_then(|difference| {
+                    let x = difference.checked_div(a)?;
+                    Some(x).filter(|_| difference.checked_rem(a) == Some(0))
+                });
+                if let Some(x) = x.filter(|x| last.contains(x)) {
+                    accept(x);
+                }
+            }
+            // constant, higher powers, or overflow working them out
+            _ => last.clone().for_each(accept),
+        }
+    }
+}
+
+impl fmt::Display for Formula {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let named = Named {
+            polynomial: &self.value,
+            addresses: &self.parameters,
+        };
+        write!(f, "[{}] = {}", self.address, named)
+    }
+}
+
+/// Runs a program made of additions and multiplications with the cells at
+/// `parameters` as variables and returns the final value of `address`.
+/// Reads from addresses that depend on the parameters yield unknown values,
+/// which is fine as long as they don't end up in the result.
+pub fn evaluate(
+    program: &[i64],
+    parameters: &[usize],
+    address: usize,
+) -> Result<Formula, SymbolicError> {
+    let variables = parameters.len();
+    let mut memory: Vec<Option<Polynomial>> = program
+        .iter()
+        .map(|&value| Some(Polynomial::constant(value, variables)))
+        .collect();
+    for (index, &parameter) in parameters.iter().enumerate() {
+        if parameter >= memory.len() {
+            memory.resize(parameter + 1, Some(Polynomial::constant(0, variables)));
+        }
+        memory[parameter] = Some(Polynomial::variable(index, variables));
+    }
+    let zero = Some(Polynomial::constant(0, variables));
+    let mut reads = vec![];
+
+    let mut pc = 0;
+    loop {
+        let cell = |memory: &Vec<Option<Polynomial>>, address: usize| {
+            memory.get(address).cloned().unwrap_or_else(|| zero.clone())
+        };
+        let instruction = cell(&memory, pc)
+            .and_then(|word| word.as_constant())
+            .ok_or(SymbolicError::VariableInstruction { pc })?;
+        let operation = parse_opcode(instruction);
+        let modes = match operation {
+            Some(Operation::Exit) => break,
+            Some(Operation::Addition {
+                summand1_mode: a,
+                summand2_mode: b,
+                destination_mode: d,
+            })
+            | Some(Operation::Multiplication {
+                factor1_mode: a,
+                factor2_mode: b,
+                destination_mode: d,
+            }) if d == AddressingMode::Indirect
+                && a != AddressingMode::Relative
+                && b != AddressingMode::Relative =>
+            {
+                [a, b]
+            }
+            _ => return Err(SymbolicError::Unsupported { pc, instruction }),
+        };
+
+        let mut operands = vec![];
+        for (offset, &mode) in modes.iter().enumerate() {
+            let parameter = cell(&memory, pc + 1 + offset);
+            operands.push(match mode {
+                AddressingMode::Immediate => parameter,
+                _ => match parameter.as_ref().and_then(Polynomial::as_constant) {
+                    Some(address) if address >= 0 => cell(&memory, address as usize),
+                    Some(_) => return Err(SymbolicError::Unsupported { pc, instruction }),
+                    None => {
+                        // the value read is unknown, but the address must be valid
+                        reads.extend(parameter);
+                        None
+                    }
+                },
+            });
+        }
+        let destination = cell(&memory, pc + 3)
+            .and_then(|word| word.as_constant())
+            .filter(|&address| address >= 0)
+            .ok_or(SymbolicError::VariableWrite { pc })? as usize;
+        let result = match (&operands[0], &operands[1]) {
+            (Some(a), Some(b)) => Some(
+                match operation {
+                    Some(Operation::Addition { .. }) => a.checked_add(b),
+                    _ => a.checked_mul(b),
+                }
+                .ok_or(SymbolicError::Overflow { pc })?,
+            ),
+            _ => None,
+        };
+        if destination >= memory.len() {
+            memory.resize(destination + 1, zero.clone());
+        }
+        memory[destination] = result;
+        pc += 4;
+    }
+
+    let value = memory
+        .get(address)
+        .cloned()
+        .unwrap_or_else(|| zero.clone())
+        .ok_or(SymbolicError::DataDependent { address })?;
+    Ok(Formula {
+        address,
+        value,
+        parameters: parameters.to_vec(),
+        reads,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::parse_program;
+
+    #[test]
+    fn test_day2_style_formula() {
+        // [3] = [[1]] + [[2]] is clobbered straight away, then
+        // [0] = ([1] + [2]) * 5 * [1] + 7
+        let program = parse_program("1,0,0,3,1,1,2,3,1002,3,5,3,2,3,1,0,1001,0,7,0,99");
+        let formula = evaluate(&program, &[1, 2], 0).unwrap();
+        assert_eq!(formula.to_string(), "[0] = 5*[1]^2 + 5*[1]*[2] + 7");
+        assert_eq!(formula.value.evaluate(&[3, 4]), Some(112));
+        assert_eq!(formula.reads.len(), 2);
+
+        let solutions = formula.solve(112, &[0..100, 0..100]);
+        assert_eq!(solutions, vec![vec![1, 20], vec![3, 4]]);
+    }
+
+    #[test]
+    fn test_linear_formula() {
+        let program = parse_program("1102,0,1000,0,1001,0,0,0,99");
+        let formula = evaluate(&program, &[1, 6], 0).unwrap();
+        assert_eq!(formula.to_string(), "[0] = 1000*[1] + [6]");
+        assert_eq!(formula.solve(12_034, &[0..100, 0..100]), vec![vec![12, 34]]);
+        assert_eq!(
+            formula.solve(12_034, &[0..10, 0..100]),
+            Vec::<Vec<i64>>::new()
+        );
+
+        // -x == i64::MIN has no solution in i64
+        let formula = evaluate(&parse_program("1102,-1,0,0,99"), &[2], 0).unwrap();
+        let x = std::slice::from_ref(&(-1..0));
+        assert_eq!(formula.solve(i64::MIN, x), Vec::<Vec<i64>>::new());
+        assert_eq!(formula.solve(1, x), vec![vec![-1]]);
+    }
+
+    #[test]
+    fn test_unsupported_programs() {
+        let program = parse_program("1,1,2,0,99");
+        assert_eq!(
+            evaluate(&program, &[1, 2], 0),
+            Err(SymbolicError::DataDependent { address: 0 })
+        );
+        let program = parse_program("1101,1,1,0,99");
+        assert_eq!(
+            evaluate(&program, &[3], 0),
+            Err(SymbolicError::VariableWrite { pc: 0 })
+        );
+        assert_eq!(
+            evaluate(&parse_program("1105,1,0"), &[1], 0),
+            Err(SymbolicError::Unsupported {
+                pc: 0,
+                instruction: 1105
+            })
+        );
+    }
+}
@@ -0,0 +1,459 @@
+use std::fmt;
+use std::io::{self, BufRead, Read, Write};
+
+use super::{parse_opcode, Memory, Operation};
+
+const MAGIC: &[u8; 4] = b"ICT1";
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct MemoryRead<W = i64> {
+    pub address: usize,
+    pub value: W,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct MemoryWrite<W = i64> {
+    pub address: usize,
+    pub old: W,
+    pub new: W,
+}
+
+/// Everything a single executed instruction did. Steps are numbered from 1.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Step<W = i64> {
+    pub number: u64,
+    pub pc: usize,
+    pub instruction: i64,
+    pub operation: Operation,
+    pub reads: Vec<MemoryRead<W>>,
+    pub write: Option<MemoryWrite<W>>,
+    /// Relative base after the instruction.
+    pub relative_base: i64,
+    pub next_pc: usize,
+}
+
+/// Receives every instruction a `Machine` executes.
+pub trait Tracer<W = i64>: Send {
+    fn record(&mut self, step: &Step<W>);
+
+    /// Flushes buffered output and reports the first write failure, if any.
+    fn finish(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Fans every step out to several tracers.
+impl Tracer for Vec<Box<dyn Tracer>> {
+    fn record(&mut self, step: &Step) {
+        for tracer in self.iter_mut() {
+            tracer.record(step);
+        }
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.iter_mut().try_for_each(|tracer| tracer.finish())
+    }
+}
+
+/// Line-oriented trace, one step per line:
+/// `7 pc=4 op=1101 ADD reads=5:3,6:4 write=10:0>7 rb=0 next=8`
+pub struct TextTrace<W> {
+    writer: W,
+    error: Option<io::Error>,
+}
+
+impl<W: Write + Send> TextTrace<W> {
+    pub fn new(writer: W) -> TextTrace<W> {
+        TextTrace {
+            writer,
+            error: None,
+        }
+    }
+}
+
+impl<W: Write + Send> Tracer for TextTrace<W> {
+    fn record(&mut self, step: &Step) {
+        if self.error.is_none() {
+            if let Err(e) = writeln!(self.writer, "{}", step) {
+                self.error = Some(e);
+            }
+        }
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        match self.error.take() {
+            Some(e) => Err(e),
+            None => self.writer.flush(),
+        }
+    }
+}
+
+/// Compact trace: a magic header followed by one record per step, with every
+/// field stored as a zigzag encoded varint.
+pub struct BinaryTrace<W> {
+    writer: W,
+    started: bool,
+    error: Option<io::Error>,
+}
+
+impl<W: Write + Send> BinaryTrace<W> {
+    pub fn new(writer: W) -> BinaryTrace<W> {
+        BinaryTrace {
+            writer,
+            started: false,
+            error: None,
+        }
+    }
+
+    fn write_step(&mut self, step: &Step) -> io::Result<()> {
+        if !self.started {
+            self.writer.write_all(MAGIC)?;
+            self.started = true;
+        }
+        let mut fields = vec![step.pc as i64, step.instruction, step.reads.len() as i64];
+        for read in &step.reads {
+            fields.push(read.address as i64);
+            fields.push(read.value);
+        }
+        match step.write {
+            Some(write) => fields.extend(&[1, write.address as i64, write.old, write.new]),
+            None => fields.push(0),
+        }
+        fields.push(step.relative_base);
+        fields.push(step.next_pc as i64);
+
+        let mut buffer = vec![];
+        for field in fields {
+            let mut value = ((field << 1) ^ (field >> 63)) as u64;
+            while value >= 0x80 {
+                buffer.push(value as u8 | 0x80);
+                value >>= 7;
+            }
+            buffer.push(value as u8);
+        }
+        self.writer.write_all(&buffer)
+    }
+}
+
+impl<W: Write + Send> Tracer for BinaryTrace<W> {
+    fn record(&mut self, step: &Step) {
+        if self.error.is_none() {
+            if let Err(e) = self.write_step(step) {
+                self.error = Some(e);
+            }
+        }
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        match self.error.take() {
+            Some(e) => Err(e),
+            None => {
+                if !self.started {
+                    self.writer.write_all(MAGIC)?;
+                    self.started = true;
+                }
+                self.writer.flush()
+            }
+        }
+    }
+}
+
+/// Reads a trace in either format, telling them apart by the binary header.
+pub fn read_trace(mut reader: impl BufRead) -> io::Result<Vec<Step>> {
+    if reader.fill_buf()?.starts_with(MAGIC) {
+        read_binary(reader)
+    } else {
+        read_text(reader)
+    }
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn read_binary(mut reader: impl Read) -> io::Result<Vec<Step>> {
+    let mut bytes = vec![];
+    reader.read_to_end(&mut bytes)?;
+    if !bytes.starts_with(MAGIC) {
+        return Err(invalid("missing trace header".to_string()));
+    }
+    let mut decoder = Decoder {
+        bytes: &bytes,
+        position: MAGIC.len(),
+    };
+
+    let mut steps = vec![];
+    while decoder.position < bytes.len() {
+        let pc = decoder.next()? as usize;
+        let instruction = decoder.next()?;
+        let reads = (0..decoder.next()?)
+            .map(|_| {
+                Ok(MemoryRead {
+                    address: decoder.next()? as usize,
+                    value: decoder.next()?,
+                })
+            })
+            .collect::<io::Result<_>>()?;
+        let write = match decoder.next()? {
+            0 => None,
+            _ => Some(MemoryWrite {
+                address: decoder.next()? as usize,
+                old: decoder.next()?,
+                new: decoder.next()?,
+            }),
+        };
+        steps.push(Step {
+            number: steps.len() as u64 + 1,
+            pc,
+            instruction,
+            operation: decode(instruction)?,
+            reads,
+            write,
+            relative_base: decoder.next()?,
+            next_pc: decoder.next()? as usize,
+        });
+    }
+    Ok(steps)
+}
+
+struct Decoder<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Decoder<'a> {
+    fn next(&mut self) -> io::Result<i64> {
+        let mut value = 0u64;
+        let mut shift = 0;
+        loop {
+            let byte = *self
+                .bytes
+                .get(self.position)
+                .ok_or_else(|| invalid("truncated trace".to_string()))?;
+            self.position += 1;
+            if shift >= 64 {
+                return Err(invalid("number too long in trace".to_string()));
+            }
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok((value >> 1) as i64 ^ -((value & 1) as i64));
+            }
+            shift += 7;
+        }
+    }
+}
+
+fn read_text(reader: impl BufRead) -> io::Result<Vec<Step>> {
+    let mut steps = vec![];
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        steps.push(parse_step(&line).ok_or_else(|| invalid(format!("invalid line {}", line)))?);
+    }
+    Ok(steps)
+}
+
+fn parse_step(line: &str) -> Option<Step> {
+    let mut tokens = line.split_whitespace();
+    let number = tokens.next()?.parse().ok()?;
+    let (mut pc, mut instruction, mut relative_base, mut next_pc) = (None, None, None, None);
+    let mut reads = vec![];
+    let mut write = None;
+    for token in tokens {
+        let (key, value) = match token.split_once('=') {
+            Some(pair) => pair,
+            // the mnemonic is only there for humans
+            None => continue,
+        };
+        match key {
+            "pc" => pc = value.parse().ok(),
+            "op" => instruction = value.parse().ok(),
+            "rb" => relative_base = value.parse().ok(),
+            "next" => next_pc = value.parse().ok(),
+            "reads" if value != "-" => {
+                for read in value.split(',') {
+                    let (address, value) = read.split_once(':')?;
+                    reads.push(MemoryRead {
+                        address: address.parse().ok()?,
+                        value: value.parse().ok()?,
+                    });
+                }
+            }
+            "write" if value != "-" => {
+                let (address, change) = value.split_once(':')?;
+                let (old, new) = change.split_once('>')?;
+                write = Some(MemoryWrite {
+                    address: address.parse().ok()?,
+                    old: old.parse().ok()?,
+                    new: new.parse().ok()?,
+                });
+            }
+            _ => (),
+        }
+    }
+    let instruction = instruction?;
+    Some(Step {
+        number,
+        pc: pc?,
+        instruction,
+        operation: parse_opcode(instruction)?,
+        reads,
+        write,
+        relative_base: relative_base?,
+        next_pc: next_pc?,
+    })
+}
+
+fn decode(instruction: i64) -> io::Result<Operation> {
+    parse_opcode(instruction).ok_or_else(|| invalid(format!("invalid opcode {}", instruction)))
+}
+
+/// Machine state reconstructed from a trace.
+pub struct ReplayState {
+    pub memory: Memory,
+    pub program_counter: usize,
+    pub relative_base: i64,
+}
+
+/// Rebuilds the state of `program` after the first `until` steps of `steps`,
+/// checking that the trace actually belongs to the program.
+pub fn replay(program: Vec<i64>, steps: &[Step], until: u64) -> Result<ReplayState, String> {
+    let mut state = ReplayState {
+        memory: Memory::from(program),
+        program_counter: 0,
+        relative_base: 0,
+    };
+    for step in steps.iter().take_while(|step| step.number <= until) {
+        if step.pc != state.program_counter || step.instruction != state.memory.read(step.pc) {
+            return Err(format!(
+                "trace diverges from program at step {}",
+                step.number
+            ));
+        }
+        if let Some(write) = step.write {
+            if state.memory.read(write.address) != write.old {
+                return Err(format!(
+                    "trace diverges from memory at step {}",
+                    step.number
+                ));
+            }
+            state.memory.write(write.address, write.new);
+        }
+        state.program_counter = step.next_pc;
+        state.relative_base = step.relative_base;
+    }
+    Ok(state)
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} pc={} op={} {} reads=",
+            self.number,
+            self.pc,
+            self.instruction,
+            self.operation.mnemonic()
+        )?;
+        if self.reads.is_empty() {
+            write!(f, "-")?;
+        }
+        for (i, read) in self.reads.iter().enumerate() {
+            let separator = if i == 0 { "" } else { "," };
+            write!(f, "{}{}:{}", separator, read.address, read.value)?;
+        }
+        match self.write {
+            Some(write) => write!(f, " write={}:{}>{}", write.address, write.old, write.new)?,
+            None => write!(f, " write=-")?,
+        }
+        write!(f, " rb={} next={}", self.relative_base, self.next_pc)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::{parse_program, Machine, RunMode};
+    use std::sync::{Arc, Mutex};
+
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn trace(program: &[i64], binary: bool) -> Vec<u8> {
+        let buffer = Arc::new(Mutex::new(vec![]));
+        let mut machine = Machine::new(program.to_vec());
+        machine.push_input(8);
+        if binary {
+            machine.set_tracer(Box::new(BinaryTrace::new(Shared(buffer.clone()))));
+        } else {
+            machine.set_tracer(Box::new(TextTrace::new(Shared(buffer.clone()))));
+        }
+        machine.run(RunMode::ToHalt).unwrap();
+        machine.take_tracer().unwrap().finish().unwrap();
+        let bytes = buffer.lock().unwrap().clone();
+        bytes
+    }
+
+    #[test]
+    fn test_text_trace() {
+        let text = trace(&parse_program("3,9,8,9,10,9,4,9,99,-1,8"), false);
+        assert_eq!(
+            String::from_utf8(text).unwrap(),
+            "1 pc=0 op=3 IN reads=- write=9:-1>8 rb=0 next=2\n\
+             2 pc=2 op=8 EQ reads=9:8,10:8 write=9:8>1 rb=0 next=6\n\
+             3 pc=6 op=4 OUT reads=9:1 write=- rb=0 next=8\n\
+             4 pc=8 op=99 HLT reads=- write=- rb=0 next=8\n"
+        );
+    }
+
+    #[test]
+    fn test_formats_agree() {
+        let program = parse_program("109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99");
+        let text = trace(&program, false);
+        let binary = trace(&program, true);
+        assert!(binary.len() * 3 < text.len());
+        assert_eq!(
+            read_trace(&binary[..]).unwrap(),
+            read_trace(&text[..]).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_corrupt_number() {
+        let mut bytes = vec![0x80; 10];
+        bytes.push(0x01);
+        let mut decoder = Decoder {
+            bytes: &bytes,
+            position: 0,
+        };
+        let error = decoder.next().unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        let mut decoder = Decoder {
+            bytes: &[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01],
+            position: 0,
+        };
+        assert_eq!(decoder.next().unwrap(), i64::MIN);
+    }
+
+    #[test]
+    fn test_replay() {
+        let program = parse_program("1101,1,2,9,1001,9,3,9,99,0");
+        let steps = read_trace(&trace(&program, true)[..]).unwrap();
+        let state = replay(program.clone(), &steps, 1).unwrap();
+        assert_eq!((state.program_counter, state.memory[9]), (4, 3));
+        let state = replay(program.clone(), &steps, 3).unwrap();
+        assert_eq!((state.program_counter, state.memory[9]), (8, 6));
+        assert!(replay(parse_program("1101,1,1,9,99"), &steps, 3).is_err());
+    }
+}
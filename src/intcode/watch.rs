@@ -0,0 +1,121 @@
+use std::fmt;
+
+use super::trace::{MemoryRead, MemoryWrite};
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum WatchKind {
+    Read,
+    Write,
+    ReadWrite,
+}
+
+/// Pauses the machine whenever an instruction touches `start..end`.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Watchpoint {
+    pub start: usize,
+    pub end: usize,
+    pub kind: WatchKind,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Access<W = i64> {
+    Read { value: W },
+    Write { old: W, new: W },
+}
+
+/// A watched cell being touched by the instruction at `pc`.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct WatchHit<W = i64> {
+    pub pc: usize,
+    pub instruction: i64,
+    pub address: usize,
+    pub access: Access<W>,
+}
+
+impl Watchpoint {
+    pub fn new(start: usize, end: usize, kind: WatchKind) -> Watchpoint {
+        Watchpoint { start, end, kind }
+    }
+
+    fn covers(&self, address: usize) -> bool {
+        self.start <= address && address < self.end
+    }
+
+    fn watches_reads(&self) -> bool {
+        self.kind != WatchKind::Write
+    }
+
+    fn watches_writes(&self) -> bool {
+        self.kind != WatchKind::Read
+    }
+}
+
+/// Finds the first watched access of an instruction, preferring its write
+/// over its reads.
+pub fn check<W: Clone>(
+    watchpoints: &[Watchpoint],
+    pc: usize,
+    instruction: i64,
+    reads: &[MemoryRead<W>],
+    write: Option<&MemoryWrite<W>>,
+) -> Option<WatchHit<W>> {
+    let hit = |address, access| WatchHit {
+        pc,
+        instruction,
+        address,
+        access,
+    };
+    if let Some(write) = write {
+        if watchpoints
+            .iter()
+            .any(|watch| watch.watches_writes() && watch.covers(write.address))
+        {
+            return Some(hit(
+                write.address,
+                Access::Write {
+                    old: write.old.clone(),
+                    new: write.new.clone(),
+                },
+            ));
+        }
+    }
+    reads
+        .iter()
+        .find(|read| {
+            watchpoints
+                .iter()
+                .any(|watch| watch.watches_reads() && watch.covers(read.address))
+        })
+        .map(|read| {
+            let value = read.value.clone();
+            hit(read.address, Access::Read { value })
+        })
+}
+
+impl fmt::Display for Watchpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let kind = match self.kind {
+            WatchKind::Read => "r",
+            WatchKind::Write => "w",
+            WatchKind::ReadWrite => "rw",
+        };
+        write!(f, "{:04}..{:04} {}", self.start, self.end, kind)
+    }
+}
+
+impl<W: fmt::Display> fmt::Display for WatchHit<W> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.access {
+            Access::Read { value } => write!(
+                f,
+                "[{}] read by {} at pc {}: {}",
+                self.address, self.instruction, self.pc, value
+            ),
+            Access::Write { old, new } => write!(
+                f,
+                "[{}] written by {} at pc {}: {} -> {}",
+                self.address, self.instruction, self.pc, old, new
+            ),
+        }
+    }
+}
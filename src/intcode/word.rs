@@ -0,0 +1,250 @@
+use std::fmt;
+use std::num::Wrapping;
+
+use num_bigint::BigInt;
+use num_traits::{ToPrimitive, Zero};
+
+/// A memory cell of a `Machine`. Addresses, the relative base and
+/// instructions always have to fit an `i64`; only the values a program
+/// computes with use the full range of the type.
+pub trait Word: Clone + PartialEq + PartialOrd + fmt::Display + fmt::Debug + 'static {
+    fn from_i64(value: i64) -> Self;
+
+    /// The value as an `i64`, or `None` if it is out of range.
+    fn to_i64(&self) -> Option<i64>;
+
+    /// The nearest `i64`, for error reports that can't hold the full value.
+    fn saturating_i64(&self) -> i64 {
+        match self.to_i64() {
+            Some(value) => value,
+            None if *self < Self::from_i64(0) => i64::MIN,
+            None => i64::MAX,
+        }
+    }
+
+    /// `None` signals overflow and stops the machine.
+    fn checked_add(&self, other: &Self) -> Option<Self>;
+
+    fn checked_mul(&self, other: &Self) -> Option<Self>;
+
+    fn is_zero(&self) -> bool;
+
+    fn parse(text: &str) -> Option<Self>;
+}
+
+/// Reports overflow as `IntcodeError::Overflow`.
+impl Word for i64 {
+    fn from_i64(value: i64) -> i64 {
+        value
+    }
+
+    fn to_i64(&self) -> Option<i64> {
+        Some(*self)
+    }
+
+    fn checked_add(&self, other: &i64) -> Option<i64> {
+        i64::checked_add(*self, *other)
+    }
+
+    fn checked_mul(&self, other: &i64) -> Option<i64> {
+        i64::checked_mul(*self, *other)
+    }
+
+    fn is_zero(&self) -> bool {
+        *self == 0
+    }
+
+    fn parse(text: &str) -> Option<i64> {
+        text.parse().ok()
+    }
+}
+
+/// Two's complement arithmetic, like a release build of the plain `i64` code.
+impl Word for Wrapping<i64> {
+    fn from_i64(value: i64) -> Wrapping<i64> {
+        Wrapping(value)
+    }
+
+    fn to_i64(&self) -> Option<i64> {
+        Some(self.0)
+    }
+
+    fn checked_add(&self, other: &Wrapping<i64>) -> Option<Wrapping<i64>> {
+        Some(*self + *other)
+    }
+
+    fn checked_mul(&self, other: &Wrapping<i64>) -> Option<Wrapping<i64>> {
+        Some(*self * *other)
+    }
+
+    fn is_zero(&self) -> bool {
+        self.0 == 0
+    }
+
+    fn parse(text: &str) -> Option<Wrapping<i64>> {
+        text.parse().ok().map(Wrapping)
+    }
+}
+
+impl Word for i128 {
+    fn from_i64(value: i64) -> i128 {
+        value.into()
+    }
+
+    fn to_i64(&self) -> Option<i64> {
+        ToPrimitive::to_i64(self)
+    }
+
+    fn checked_add(&self, other: &i128) -> Option<i128> {
+        i128::checked_add(*self, *other)
+    }
+
+    fn checked_mul(&self, other: &i128) -> Option<i128> {
+        i128::checked_mul(*self, *other)
+    }
+
+    fn is_zero(&self) -> bool {
+        *self == 0
+    }
+
+    fn parse(text: &str) -> Option<i128> {
+        text.parse().ok()
+    }
+}
+
+/// Never overflows.
+impl Word for BigInt {
+    fn from_i64(value: i64) -> BigInt {
+        value.into()
+    }
+
+    fn to_i64(&self) -> Option<i64> {
+        ToPrimitive::to_i64(self)
+    }
+
+    fn checked_add(&self, other: &BigInt) -> Option<BigInt> {
+        Some(self + other)
+    }
+
+    fn checked_mul(&self, other: &BigInt) -> Option<BigInt> {
+        Some(self * other)
+    }
+
+    fn is_zero(&self) -> bool {
+        Zero::is_zero(self)
+    }
+
+    fn parse(text: &str) -> Option<BigInt> {
+        text.parse().ok()
+    }
+}
+
+/// Like `parse_program`, for any word type.
+pub fn parse_words<W: Word>(input: &str) -> Vec<W> {
+    input
+        .split(',')
+        .filter_map(|value| W::parse(value.trim()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::{parse_program, IntcodeError, Machine, ProgramResult, RunMode};
+    use std::collections::VecDeque;
+
+    fn run<W: Word>(program: &str, input: &[W]) -> Result<Vec<W>, IntcodeError> {
+        let mut machine = Machine::with_devices(
+            parse_words(program),
+            input.iter().cloned().collect::<VecDeque<W>>(),
+            vec![],
+        );
+        machine.run(RunMode::ToHalt)?;
+        Ok(machine.output().clone())
+    }
+
+    const SQUARE: &str = "3,11,2,11,11,11,4,11,99,0,0,0";
+
+    #[test]
+    fn test_checked_overflow() {
+        assert_eq!(
+            run::<i64>(SQUARE, &[3_037_000_499]),
+            Ok(vec![9_223_372_030_926_249_001])
+        );
+        assert_eq!(
+            run::<i64>(SQUARE, &[3_037_000_500]),
+            Err(IntcodeError::Overflow {
+                pc: 2,
+                instruction: 2
+            })
+        );
+        assert_eq!(
+            run::<i64>("109,9223372036854775807,109,1,99", &[]),
+            Err(IntcodeError::Overflow {
+                pc: 2,
+                instruction: 109
+            })
+        );
+    }
+
+    #[test]
+    fn test_wrapping() {
+        assert_eq!(run(SQUARE, &[Wrapping(1i64 << 32)]), Ok(vec![Wrapping(0)]));
+        assert_eq!(
+            run(SQUARE, &[Wrapping(3_037_000_500)]),
+            Ok(vec![Wrapping(3_037_000_500i64.wrapping_mul(3_037_000_500))])
+        );
+    }
+
+    #[test]
+    fn test_wide_words() {
+        assert_eq!(run(SQUARE, &[1i128 << 62]), Ok(vec![1i128 << 124]));
+        assert!(run(SQUARE, &[1i128 << 64]).is_err());
+
+        let big = BigInt::parse("123456789012345678901234567890").unwrap();
+        assert_eq!(
+            run(SQUARE, std::slice::from_ref(&big)),
+            Ok(vec![&big * &big])
+        );
+        // values that don't fit an address are still an error
+        assert_eq!(
+            run::<BigInt>("4,99999999999999999999,99", &[]),
+            Err(IntcodeError::Overflow {
+                pc: 0,
+                instruction: 4
+            })
+        );
+        assert_eq!(
+            run::<BigInt>("99999999999999999999", &[]),
+            Err(IntcodeError::InvalidOpcode {
+                pc: 0,
+                value: i64::MAX
+            })
+        );
+    }
+
+    #[test]
+    fn test_agrees_with_machine() {
+        let quine = "109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99";
+        let mut machine = Machine::new(parse_program(quine));
+        assert_eq!(machine.run(RunMode::ToHalt), Ok(ProgramResult::Finished));
+        assert_eq!(&run::<i64>(quine, &[]).unwrap(), machine.output());
+        let wide: Vec<i128> = machine.output().iter().map(|&value| value.into()).collect();
+        assert_eq!(run::<i128>(quine, &[]), Ok(wide));
+
+        let program = parse_words::<BigInt>("3,0,4,0,99");
+        let mut words = Machine::with_devices(program, VecDeque::new(), vec![]);
+        assert_eq!(
+            words.run(RunMode::UntilInput),
+            Ok(ProgramResult::NeedsInput)
+        );
+        let big = BigInt::from(1u128 << 65);
+        words.push_input(big.clone());
+        assert_eq!(
+            words.run(RunMode::UntilOutput),
+            Ok(ProgramResult::Output(big))
+        );
+        assert_eq!(words.run(RunMode::ToHalt), Ok(ProgramResult::Finished));
+        assert_eq!(words.steps(), 3);
+    }
+}
@@ -1,6 +1,6 @@
 extern crate clap;
 extern crate termion;
-use clap::{App, SubCommand};
+use clap::{App, Arg, SubCommand};
 
 mod day1;
 mod day2;
@@ -10,27 +10,38 @@ mod day5;
 mod day6;
 mod day7;
 mod day8;
+mod intcode;
 
 fn main() {
     let matches = App::new("advent")
         .version("1.0")
         .author("Jupp Mueller <jupp0r@gmail.com>")
         .about("Advent of code 2019")
+        .arg(
+            Arg::with_name("profile")
+                .long("profile")
+                .global(true)
+                .help("Prints Intcode execution statistics to stderr at exit"),
+        )
         .subcommand(SubCommand::with_name("day1"))
-        .subcommand(SubCommand::with_name("day2"))
+        .subcommand(day2::subcommand())
         .subcommand(SubCommand::with_name("day3"))
         .subcommand(SubCommand::with_name("day4"))
-        .subcommand(SubCommand::with_name("day5"))
+        .subcommand(day5::subcommand())
         .subcommand(SubCommand::with_name("day6"))
-        .subcommand(SubCommand::with_name("day7"))
+        .subcommand(day7::subcommand())
         .subcommand(SubCommand::with_name("day8"))
+        .subcommand(intcode::cli::subcommand())
         .get_matches();
+    if matches.is_present("profile") {
+        intcode::profile::enable();
+    }
 
     if matches.subcommand_matches("day1").is_some() {
         day1::run();
     }
-    if matches.subcommand_matches("day2").is_some() {
-        day2::run();
+    if let Some(matches) = matches.subcommand_matches("day2") {
+        day2::run(matches);
     }
     if matches.subcommand_matches("day3").is_some() {
         day3::run();
@@ -38,16 +49,22 @@ fn main() {
     if matches.subcommand_matches("day4").is_some() {
         day4::run();
     }
-    if matches.subcommand_matches("day5").is_some() {
-        day5::run();
+    if let Some(matches) = matches.subcommand_matches("day5") {
+        day5::run(matches);
     }
     if matches.subcommand_matches("day6").is_some() {
         day6::run();
     }
-    if matches.subcommand_matches("day7").is_some() {
-        day7::run();
+    if let Some(matches) = matches.subcommand_matches("day7") {
+        day7::run(matches);
     }
     if matches.subcommand_matches("day8").is_some() {
         day8::run();
     }
+    if let Some(matches) = matches.subcommand_matches("intcode") {
+        intcode::cli::run(matches);
+    }
+    if let Some(profile) = intcode::profile::collected() {
+        eprint!("{}", profile);
+    }
 }